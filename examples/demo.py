@@ -11,6 +11,7 @@ from wukong import *
 def on_init():
     print("on_init")
     print(trade_time())
+    subscribe("BTCUSDT", TimeFrame.Minute15)
 
 
 ########################################################################################
@@ -58,8 +59,43 @@ def on_day_end():
 ########################################################################################
 # tick
 ########################################################################################
-def on_tick():
-    print("on_tick")
+def on_tick(tick):
+    print("on_tick", tick.symbol, tick.price)
+
+
+########################################################################################
+# K线收盘
+########################################################################################
+def on_bar(symbol, timeframe, candle):
+    print("on_bar", symbol, timeframe, candle.close)
+
+
+########################################################################################
+# 订单状态变化
+########################################################################################
+def on_order(order):
+    print("on_order", order.id, order.status)
+
+
+########################################################################################
+# 订单成交
+########################################################################################
+def on_trade(trade):
+    print("on_trade", trade.order_id, trade.price, trade.size)
+
+
+########################################################################################
+# 交易对池变化
+########################################################################################
+def on_universe_change(added, removed):
+    print("on_universe_change", added, removed)
+
+
+########################################################################################
+# 热重载
+########################################################################################
+def on_reload():
+    print("on_reload")
 
 
 ########################################################################################
@@ -73,7 +109,12 @@ def on_stop():
 # 运行策略
 ########################################################################################
 def main():
-    run(BACKTEST, __file__)
+    config = Config(
+        begin=str_to_date("20240101"),
+        end=str_to_date("20240102"),
+        symbols=["BTCUSDT"],
+    )
+    run(BACKTEST, __file__, config)
 
 
 if __name__ == "__main__":
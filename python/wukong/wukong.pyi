@@ -1,7 +1,8 @@
 from enum import Enum, auto
 from decimal import Decimal
 from datetime import datetime
-from typing import List, Optional
+from typing import Any, Dict, List, Optional, Tuple
+import polars as pl
 
 BANNER: str = ...
 """
@@ -54,6 +55,133 @@ class Side(Enum):
     做空
     """
 
+class Market(Enum):
+    """
+    市场类型
+    ---
+    现货交易对名称为 `基础资产/计价资产`, 如 `BTC/USDT`, 与同名合约区分
+    """
+
+    UsdM = auto()
+    """
+    U本位合约
+    """
+    Spot = auto()
+    """
+    现货
+    ---
+    - 以 `Side.Long` 开仓单买入, 减仓单卖出, 不支持做空
+    - 无杠杆及保证金, 买入扣除计价资产, 卖出扣除基础资产
+    - 持仓仅记录成本, 资产计入钱包余额, 按标记价格估值计入权益
+    - 暂不支持实盘
+    """
+    CoinM = auto()
+    """
+    币本位合约, 含永续及交割合约
+    ---
+    - 数量为合约张数, 保证金、手续费及盈亏以结算币计
+    - 做多盈亏为 `数量 * 面值 * (1 / 开仓价 - 1 / 平仓价)`
+    - 账户保证金及未实现盈亏按结算币标记价格折算
+    - 暂不支持实盘
+    """
+
+class MarginMode(Enum):
+    """
+    保证金模式
+    """
+
+    Cross = auto()
+    """
+    全仓, 仓位共用结算资产可用余额
+    """
+    Isolated = auto()
+    """
+    逐仓, 仓位亏损以仓位保证金为限
+    """
+
+class FeeAsset(Enum):
+    """
+    现货手续费扣除资产
+    """
+
+    Received = auto()
+    """
+    收到的资产, 买入扣基础资产, 卖出扣计价资产
+    """
+    Quote = auto()
+    """
+    计价资产, 买单按吃单费率额外冻结手续费
+    """
+
+class Intrabar(Enum):
+    """
+    K线内价格路径假设, 决定同一根K线内挂单的成交顺序
+    """
+
+    OpenHighLowClose = auto()
+    """
+    开盘-最高-最低-收盘
+    """
+    OpenLowHighClose = auto()
+    """
+    开盘-最低-最高-收盘
+    """
+    NearestFirst = auto()
+    """
+    先到达离开盘价较近的极值
+    """
+    Pessimistic = auto()
+    """
+    先到达对持仓不利的极值, 无持仓时同 `NearestFirst`
+    """
+    DrillDown = auto()
+    """
+    按数据库中的归集成交还原路径
+    ---
+    - 1分钟K线已是最小周期, 仅使用归集成交, 不使用更小周期K线
+    - 没有归集成交数据时同 `NearestFirst`, 每个交易对每天记录一次警告日志
+    """
+
+class Replay(Enum):
+    """
+    回测行情回放方式
+    """
+
+    Candle = auto()
+    """
+    分钟K线
+    """
+    AggTrade = auto()
+    """
+    逐笔归集成交, 需先通过 `import_agg_trades` 导入
+    ---
+    成交价穿过挂单价时全部成交, 等于挂单价时累计成交数量不超过该笔成交数量
+    """
+    Depth = auto()
+    """
+    逐笔归集成交及盘口, 需先通过 `import_depth` 导入盘口
+    ---
+    限价单按排队位置成交, 同价位成交数量先消耗排在前面的挂单
+    """
+
+class Resample(Enum):
+    """
+    成交重采样方式
+    """
+
+    Shuffle = auto()
+    """
+    打乱顺序
+    """
+    Bootstrap = auto()
+    """
+    有放回抽样
+    """
+    Skip = auto()
+    """
+    随机跳过
+    """
+
 class TimeFrame(Enum):
     """
     时间周期
@@ -154,9 +282,188 @@ class OrderStatus(Enum):
     已取消
     """
 
+class Candle:
+    """
+    K线
+    """
+
+    time: int
+    """
+    开盘时间
+    """
+    open: float
+    """
+    开盘价
+    """
+    high: float
+    """
+    最高价
+    """
+    low: float
+    """
+    最低价
+    """
+    close: float
+    """
+    收盘价
+    """
+    volume: float
+    """
+    数量
+    """
+    amount: float
+    """
+    金额
+    """
+    taker_volume: float
+    """
+    吃单数量
+    """
+    taker_amount: float
+    """
+    吃单金额
+    """
+    trades: int
+    """
+    成交笔数
+    """
+
+class Tick:
+    """
+    行情事件
+    ---
+    策略定义 `on_tick(tick)` 时逐个交易对触发, 定义 `on_tick()` 时每分钟触发一次
+    """
+
+    symbol: str
+    """
+    交易对
+    """
+    time: datetime
+    """
+    时间
+    """
+    price: Decimal
+    """
+    最新价格
+    """
+    size: Decimal
+    """
+    成交数量
+    """
+
+class Book:
+    """
+    盘口
+    """
+
+    symbol: str
+    time: datetime
+    """
+    更新时间
+    """
+    bids: List[Tuple[Decimal, Decimal]]
+    """
+    买盘 (价格, 数量), 价格从高到低
+    """
+    asks: List[Tuple[Decimal, Decimal]]
+    """
+    卖盘 (价格, 数量), 价格从低到高
+    """
+
+class Trade:
+    """
+    成交
+    ---
+    每次成交(含部分成交)触发 `on_trade(trade)`, 随后触发 `on_order(order)`
+    ---
+    实盘定时从交易所同步订单状态及成交, 手续费取交易所实际扣除
+    """
+
+    symbol: str
+    """
+    交易对
+    """
+    order_id: str
+    """
+    订单ID
+    """
+    time: datetime
+    """
+    成交时间
+    """
+    side: Side
+    """
+    方向
+    """
+    reduce: bool
+    """
+    是否减仓
+    """
+    price: Decimal
+    """
+    成交价格
+    """
+    size: Decimal
+    """
+    成交数量
+    """
+    fee: Decimal
+    """
+    手续费, 以结算资产计
+    """
+    fee_asset: str
+    """
+    实际扣除手续费的资产
+    """
+    pnl: Decimal
+    """
+    平仓盈亏, 以结算资产计, 开仓为0
+    """
+    settle_price: Decimal
+    """
+    成交时结算资产的价格, 以资金资产计, 报告按此折算盈亏及手续费
+    """
+    maker: bool
+    """
+    是否挂单成交
+    """
+    intrabar: Optional[Intrabar]
+    """
+    按K线撮合时使用的K线内价格路径假设, 其他方式成交时为 `None`
+    """
+
+class OrderAmend:
+    """
+    订单修改记录
+    """
+
+    time: datetime
+    """
+    修改时间
+    """
+    old_price: Decimal
+    """
+    修改前价格
+    """
+    old_size: Decimal
+    """
+    修改前数量
+    """
+    price: Decimal
+    """
+    修改后价格
+    """
+    size: Decimal
+    """
+    修改后数量
+    """
+
 class Order:
     """
     订单
+    ---
+    订单状态每次变化时触发 `on_order(order)`
     """
 
     symbol: str
@@ -215,6 +522,405 @@ class Order:
     """
     状态
     """
+    amends: List[OrderAmend]
+    """
+    修改记录
+    """
+    reason: Optional[str]
+    """
+    拒绝原因
+    """
+    strategy: str
+    """
+    所属策略
+    """
+    queue: Optional[Decimal]
+    """
+    排队位置, 同价位排在前面的挂单数量, 仅按盘口回放时有效
+    """
+
+class SymbolInfo:
+    """
+    交易对规则
+    """
+
+    symbol: str
+    """
+    交易对, 现货为 `基础资产/计价资产`
+    """
+    market: Market
+    """
+    市场类型
+    """
+    base_asset: str
+    """
+    基础资产
+    """
+    quote_asset: str
+    """
+    计价资产
+    """
+    settle_asset: str
+    """
+    结算资产, 保证金、手续费及盈亏以此计, 现货为计价资产
+    """
+    contract_size: Decimal
+    """
+    合约面值, 仅币本位合约, 以计价资产计
+    """
+    tick_size: Decimal
+    """
+    价格最小变动单位
+    """
+    step_size: Decimal
+    """
+    数量步长
+    """
+    min_size: Decimal
+    """
+    最小数量
+    """
+    max_size: Decimal
+    """
+    最大数量
+    """
+    min_notional: Decimal
+    """
+    最小名义价值, 以结算资产计, 币本位合约按合约面值换算
+    """
+    max_leverage: Decimal
+    """
+    最大杠杆倍数, 0表示未知
+    """
+    brackets: List[Tuple[Decimal, Decimal]]
+    """
+    杠杆分层, (名义价值上限, 最大杠杆倍数), 按名义价值升序, 空表示未知
+    """
+    contract_type: str
+    """
+    合约类型
+    """
+    onboard_date: int
+    """
+    上线时间
+    """
+    delist_date: int
+    """
+    下线时间, 0表示未下线
+    """
+
+class Report:
+    """
+    绩效报告
+    """
+
+    initial_equity: float
+    """
+    初始权益, 资金加上初始钱包余额按首分钟结束时的价格估值
+    """
+    final_equity: float
+    """
+    最终权益
+    """
+    total_return: float
+    """
+    总收益率
+    """
+    annual_return: float
+    """
+    年化收益率
+    """
+    max_drawdown: float
+    """
+    最大回撤
+    """
+    sharpe: float
+    """
+    夏普比率, 按日收益率年化
+    """
+    trades: int
+    """
+    成交次数
+    """
+    win_rate: float
+    """
+    胜率, 按平仓成交统计
+    """
+    profit_factor: float
+    """
+    盈亏比, 平仓总盈利 / 平仓总亏损, 有盈利且无亏损时为 `inf`
+    """
+    fees: float
+    """
+    手续费, 按成交时的结算资产价格折算为资金资产
+    """
+    monte_carlo: Optional[MonteCarlo]
+    """
+    蒙特卡洛分析摘要, 调用 `monte_carlo` 后才有
+    """
+
+class MonteCarlo:
+    """
+    蒙特卡洛分析摘要
+    """
+
+    method: Resample
+    """
+    重采样方式
+    """
+    runs: int
+    """
+    模拟次数
+    """
+    percentiles: List[float]
+    """
+    百分位, 与收益率及回撤一一对应
+    """
+    final_return: List[float]
+    """
+    最终收益率百分位
+    """
+    max_drawdown: List[float]
+    """
+    最大回撤百分位
+    """
+    risk_of_ruin: float
+    """
+    破产概率
+    """
+
+class RiskLimits:
+    """
+    风控限制, 未设置的项不检查
+    """
+
+    max_notional: Optional[Decimal]
+    """
+    单交易对最大名义价值
+    """
+    max_leverage: Optional[Decimal]
+    """
+    账户最大总杠杆
+    """
+    max_open_orders: Optional[int]
+    """
+    最大有效订单数
+    """
+    max_order_rate: Optional[int]
+    """
+    每分钟最大下单数
+    """
+    max_daily_loss: Optional[Decimal]
+    """
+    单日最大亏损
+    ---
+    以UTC每日开始时的权益为基准
+    """
+    price_band: Optional[Decimal]
+    """
+    限价偏离标记价格的最大比例
+    """
+    max_drawdown: Optional[Decimal]
+    """
+    账户最大回撤比例, 超过则暂停交易
+    """
+
+    def __init__(
+        self,
+        max_notional: Optional[Decimal] = None,
+        max_leverage: Optional[Decimal] = None,
+        max_open_orders: Optional[int] = None,
+        max_order_rate: Optional[int] = None,
+        max_daily_loss: Optional[Decimal] = None,
+        price_band: Optional[Decimal] = None,
+        max_drawdown: Optional[Decimal] = None,
+    ): ...
+
+class Latency:
+    """
+    回测订单延迟, 单位毫秒
+    ---
+    - 下单后订单保持 `Submited` 状态, 到达后才检查资金并挂单
+    - 撤单到达前订单仍可能成交
+    - 延迟按模拟时钟计算 : 分钟开始回调为分钟开始时间, 分钟结束回调为分钟结束时间, 逐笔回放时为成交时间
+    - 按K线撮合时, K线内价格路径各线段按时间均分, 订单请求按到达时间在路径上生效 : 撤单先于触及挂单价到达时撤销成功, 下单到达后按当时路径价格撮合并参与之后路径的撮合
+    """
+
+    submit: int
+    """
+    下单延迟
+    """
+    cancel: int
+    """
+    撤单延迟
+    """
+    jitter: int
+    """
+    随机延迟均值, 按指数分布叠加在固定延迟上, 0表示固定延迟
+    """
+
+    def __init__(self, submit: int = 0, cancel: int = 0, jitter: int = 0): ...
+
+class UniverseRule:
+    """
+    交易对池规则
+    """
+
+    top: Optional[int]
+    """
+    按24小时成交额取前N个交易对, 未设置则不限
+    """
+    min_listed_days: int
+    """
+    排除上线不足N天的交易对
+    """
+    contract_type: Optional[str]
+    """
+    合约类型, 未设置则不限
+    """
+    market: Market
+    """
+    市场类型
+    """
+
+    def __init__(
+        self,
+        top: Optional[int] = None,
+        min_listed_days: int = 0,
+        contract_type: Optional[str] = None,
+        market: Market = Market.UsdM,
+    ): ...
+
+class Config:
+    """
+    运行配置
+    """
+
+    cash: Decimal
+    """
+    初始资金
+    """
+    leverage: Decimal
+    """
+    默认杠杆倍数
+    """
+    maker_fee: Decimal
+    """
+    挂单手续费率
+    """
+    taker_fee: Decimal
+    """
+    吃单手续费率
+    """
+    begin: Optional[datetime]
+    """
+    回测开始时间
+    """
+    end: Optional[datetime]
+    """
+    回测结束时间, 未设置则到当前时间
+    """
+    symbols: List[str]
+    """
+    固定交易对
+    """
+    universe: Optional[UniverseRule]
+    """
+    交易对池规则, 每日重新选择, 交易对池变化时触发 `on_universe_change(added, removed)`
+    """
+    risk: RiskLimits
+    """
+    风控限制
+    """
+    halt_file: Optional[str]
+    """
+    暂停文件, 文件存在时暂停交易
+    """
+    symbol_file: Optional[str]
+    """
+    交易对规则快照, 交易所 `exchangeInfo` 格式的JSON文件
+    ---
+    未设置时, 模拟及实盘从交易所获取, 回测使用本地已保存的规则
+    """
+    seed: Optional[int]
+    """
+    随机种子, 驱动订单ID, `rand_id` 及Python `random` 模块
+    ---
+    回测未设置时为0, 相同输入产生相同的成交记录; 模拟及实盘未设置时随机
+    """
+    replay: Replay
+    """
+    回测行情回放方式
+    ---
+    - `Replay.Candle` : 按分钟K线撮合, 每分钟结束时通知 `on_tick`
+    - `Replay.AggTrade` : 逐笔撮合归集成交, 每笔成交通知 `on_tick`, 当前分钟K线由成交合成
+    - `Replay.Depth` : 在 `Replay.AggTrade` 基础上重建订单簿, 限价单按排队位置成交
+    """
+    intrabar: Intrabar
+    """
+    按K线撮合时的K线内价格路径假设
+    """
+    latency: Latency
+    """
+    回测订单延迟, 默认无延迟
+    """
+    balances: Dict[str, Decimal]
+    """
+    初始钱包余额, 如 `{"USDC": Decimal(1000), "BTC": Decimal(1)}`
+    ---
+    - 初始资金 `cash` 另计入 `USDT` 余额
+    - 同时运行多个策略时按各策略资金比例分配
+    - 报告初始权益计入此部分, 按首分钟结束时的价格估值
+    """
+    multi_assets: bool
+    """
+    联合保证金模式, U本位合约共用抵押资产折算后的保证金
+    """
+    haircuts: Dict[str, Decimal]
+    """
+    联合保证金模式下的抵押资产及折价率, 如 `{"USDC": Decimal(0), "BNB": Decimal("0.05")}`
+    ---
+    `USDT` 始终可作抵押且不折价, 未列出的资产不作抵押
+    """
+    spot_fee: FeeAsset
+    """
+    现货手续费扣除资产
+    """
+    margin_mode: MarginMode
+    """
+    合约默认保证金模式
+    """
+    maintenance_rate: Decimal
+    """
+    维持保证金率, 用于计算强平价格
+    ---
+    回测及模拟盘按K线最高最低价触及强平价格时强平
+    """
+
+    def __init__(
+        self,
+        cash: Optional[Decimal] = None,
+        leverage: Optional[Decimal] = None,
+        maker_fee: Optional[Decimal] = None,
+        taker_fee: Optional[Decimal] = None,
+        begin: Optional[datetime] = None,
+        end: Optional[datetime] = None,
+        symbols: List[str] = [],
+        universe: Optional[UniverseRule] = None,
+        risk: Optional[RiskLimits] = None,
+        halt_file: Optional[str] = None,
+        symbol_file: Optional[str] = None,
+        seed: Optional[int] = None,
+        replay: Replay = Replay.Candle,
+        intrabar: Intrabar = Intrabar.OpenHighLowClose,
+        latency: Optional[Latency] = None,
+        balances: Dict[str, Decimal] = {},
+        multi_assets: bool = False,
+        haircuts: Dict[str, Decimal] = {},
+        spot_fee: FeeAsset = FeeAsset.Received,
+        margin_mode: MarginMode = MarginMode.Cross,
+        maintenance_rate: Optional[Decimal] = None,
+    ): ...
 
 def debug(*args):
     """
@@ -267,27 +973,56 @@ def now_ms() -> int:
 
 def rand_id() -> str:
     """
-    随机32位ID
+    随机32位ID, 由运行随机种子决定
     """
 
 def account_cash() -> Decimal:
     """
-    账户资金
+    账户资金, 钱包余额按标记价格估值
+    ---
+    资产按以其为基础资产、稳定币计价的交易对标记价格估值, 现货优先; 没有交易对时稳定币按1估值, 其他按0估值
     """
 
 def account_available_cash() -> Decimal:
     """
-    账户可用资金
+    账户可用资金, U本位合约可用保证金
+    ---
+    - 默认为 `USDT` 可用余额
+    - 联合保证金模式下为抵押资产按折价率折算后, 加上U本位合约未实现盈亏, 扣除保证金及冻结
     """
 
 def account_margin() -> Decimal:
     """
-    账户占用保证金
+    账户占用保证金, 按结算资产价格折算
     """
 
 def account_pnl() -> Decimal:
     """
-    账户未实现盈亏
+    账户未实现盈亏, 不含现货, 按结算资产价格折算
+    """
+
+def account_equity() -> Decimal:
+    """
+    账户权益, 资金及未实现盈亏之和
+    """
+
+def account_balance(asset: str) -> Decimal:
+    """
+    钱包余额
+    ---
+    合约手续费及平仓盈亏计入交易对结算资产, 现货成交交换计价资产及基础资产
+    """
+
+def account_available_balance(asset: str) -> Decimal:
+    """
+    可用余额
+    ---
+    扣除现货冻结, 按结算资产计入合约未实现盈亏、保证金及冻结, 联合保证金模式下U本位合约不计入
+    """
+
+def account_balances() -> Dict[str, Decimal]:
+    """
+    全部钱包余额
     """
 
 def position_long_size(symbol: str) -> Optional[Decimal]:
@@ -315,6 +1050,11 @@ def position_long_pnl(symbol: str) -> Optional[Decimal]:
     做多仓位未实现盈亏
     """
 
+def position_long_liquidation_price(symbol: str) -> Optional[Decimal]:
+    """
+    做多仓位强平价格, 0表示不会强平
+    """
+
 def position_short_size(symbol: str) -> Optional[Decimal]:
     """
     做空仓位数量
@@ -340,11 +1080,26 @@ def position_short_pnl(symbol: str) -> Optional[Decimal]:
     做空仓位未实现盈亏
     """
 
+def position_short_liquidation_price(symbol: str) -> Optional[Decimal]:
+    """
+    做空仓位强平价格, 0表示不会强平
+    """
+
+def pair_market(symbol: str) -> Optional[Market]:
+    """
+    交易对市场类型
+    """
+
 def pair_leverage(symbol: str) -> Optional[Decimal]:
     """
     交易对杠杆倍数
     """
 
+def pair_margin_mode(symbol: str) -> Optional[MarginMode]:
+    """
+    交易对保证金模式
+    """
+
 def pair_margin(symbol: str) -> Optional[Decimal]:
     """
     交易对保证金
@@ -370,6 +1125,171 @@ def pair_order_ids(symbol: str) -> List[str]:
     交易对订单IDS
     """
 
+def place_order(
+    symbol: str,
+    type: Type,
+    side: Side,
+    size: Decimal,
+    price: Optional[Decimal] = None,
+    reduce: bool = False,
+) -> Order:
+    """
+    下单
+    ---
+    - 限价订单必须指定价格, 市价订单默认使用标记价格
+    - 下单前经过交易对规则及风控检查, 未通过则订单状态为 `Rejected` 并记录拒绝原因
+    - 回测设置 `Config.latency` 时返回 `Submited` 状态的订单, 到达后通过 `on_order` 通知
+    - 实盘提交时先通知 `Submited`, 交易所拒绝时订单状态为 `Rejected`
+    - 模拟成交时现货买单成交额加手续费超过可用资金, 订单状态为 `Rejected`
+    """
+
+def amend_order(
+    symbol: str,
+    id: str,
+    price: Optional[Decimal] = None,
+    size: Optional[Decimal] = None,
+) -> Order:
+    """
+    修改订单
+    ---
+    - 订单ID不变, 仅修改未成交部分
+    - 仅支持限价订单, 数量必须大于已成交数量
+    - 修改记录保存于 `Order.amends`
+    - 修改后的订单按下单相同的交易对规则及风控检查, 未通过时报错, 原订单不变
+    - 尚未到达的订单不能修改
+    """
+
+def cancel_order(symbol: str, id: str) -> Order:
+    """
+    撤单
+    ---
+    回测设置 `Config.latency` 时返回当前订单, 撤单到达前订单仍可能成交, 结果通过 `on_order` 通知
+    """
+
+def set_leverage(symbol: str, leverage: Decimal):
+    """
+    修改杠杆倍数
+    ---
+    - 不能超过交易对最大杠杆, 及持仓与挂单名义价值所在分层的最大杠杆
+    - 按新杠杆重算仓位及挂单保证金, 保留逐仓追加的保证金, 可用资金不足或仓位将被强平时拒绝
+    """
+
+def set_margin_mode(symbol: str, mode: MarginMode):
+    """
+    修改保证金模式
+    ---
+    仅合约, 且交易对无持仓及有效订单
+    """
+
+def add_margin(symbol: str, side: Side, amount: Decimal):
+    """
+    增加逐仓保证金, 不能超过可用余额
+    """
+
+def reduce_margin(symbol: str, side: Side, amount: Decimal):
+    """
+    减少逐仓保证金
+    ---
+    减少后仓位保证金不能低于按标记价格计算的初始保证金加未实现亏损
+    """
+
+def halt(reason: str):
+    """
+    暂停交易
+    ---
+    - 撤销所有交易对的有效订单
+    - 以减仓市价单平掉所有多空仓位
+    - 停止运行并调用 `on_stop`
+    - 调用 `resume` 前无法下单
+    """
+
+def resume():
+    """
+    恢复交易
+    ---
+    清除暂停状态并恢复运行, 账户回撤峰值重置为当前权益
+    """
+
+def is_halted() -> bool:
+    """
+    是否已暂停交易
+    """
+
+def symbol_info(symbol: str) -> Optional[SymbolInfo]:
+    """
+    交易对规则
+    """
+
+def round_price(symbol: str, price: Decimal) -> Decimal:
+    """
+    价格按最小变动单位四舍五入
+    """
+
+def round_size(symbol: str, size: Decimal) -> Decimal:
+    """
+    数量按步长向下取整
+    ---
+    - 超过最大数量时取最大数量
+    - 小于最小数量时返回0
+    """
+
+def size_by_cash(symbol: str, cash: Decimal) -> Decimal:
+    """
+    按资金计算下单数量
+    ---
+    数量 = 资金 * 杠杆 / 标记价格, 已按交易对规则取整
+    """
+
+def size_by_risk(symbol: str, side: Side, stop_distance: Decimal, risk_pct: Decimal) -> Decimal:
+    """
+    按风险计算下单数量
+    ---
+    数量 = 可用保证金 * 风险比例 / 单位数量止损亏损, 已按交易对规则取整
+    ---
+    - 止损价做多为标记价格减止损距离, 做空为标记价格加止损距离
+    - U本位单位亏损为止损距离, 币本位合约按合约面值以结算资产计算, 多空不对称
+    """
+
+def size_by_volatility(symbol: str, side: Side, target_vol: Decimal, atr: Decimal) -> Decimal:
+    """
+    按波动率计算下单数量
+    ---
+    数量 = 可用保证金 * 目标波动率 / 单位数量ATR不利波动, 已按交易对规则取整
+    ---
+    - 不利波动做多为价格下跌ATR, 做空为价格上涨ATR
+    - U本位单位波动为ATR, 币本位合约按合约面值以结算资产计算, 多空不对称
+    """
+
+def size_by_kelly(
+    symbol: str,
+    win_rate: Decimal,
+    win_loss_ratio: Decimal,
+    fraction: Decimal = Decimal(1),
+) -> Decimal:
+    """
+    按凯利公式计算下单数量
+    ---
+    - 凯利比例 = 胜率 - (1 - 胜率) / 盈亏比
+    - 数量按 可用资金 * 凯利比例 * 系数 计算, 已按交易对规则取整
+    """
+
+def subscribe(symbol: str, timeframe: TimeFrame):
+    """
+    订阅K线
+    ---
+    K线收盘时触发 `on_bar(symbol, timeframe, candle)`
+    """
+
+def unsubscribe(symbol: str, timeframe: TimeFrame):
+    """
+    取消订阅K线
+    """
+
+def strategy_name() -> str:
+    """
+    当前策略名称, 即策略文件名
+    """
+
 def is_running() -> bool:
     """
     是否运行中
@@ -390,7 +1310,132 @@ def symbols() -> List[str]:
     所有交易对
     """
 
-def run(mode: Mode, strategy: str):
+def run(mode: Mode, strategy: str, config: Optional[Config] = None):
     """
     运行策略
+    ---
+    每次运行创建新的引擎, 同一进程可重复运行, 运行结束后账户等函数返回本次运行的结果
+
+    模拟及实盘模式下, 策略文件修改后自动重载
+    - 保留策略模块中的变量及引擎中的订单和仓位
+    - 重载后执行 `on_reload` (如有定义), 不再执行 `on_init`
+    - 编译或 `on_reload` 失败时回滚到原策略的回调函数, `on_reload` 中已修改的变量及共享对象不会恢复
+    """
+
+def run_portfolio(
+    mode: Mode,
+    strategies: List[Tuple[str, Decimal]],
+    config: Optional[Config] = None,
+):
+    """
+    同时运行多个策略
+    ---
+    - `strategies` 为 `(策略文件, 资金)` 列表, 每个策略拥有独立的子账户, 资金覆盖 `Config.cash`
+    - 各策略共享行情、数据库及时钟, 订单按策略标记, 盈亏按子账户统计
+    - 账户、仓位及订单相关函数返回当前回调所属策略的数据
+    """
+
+def book(symbol: str, depth: int = 10) -> Optional[Book]:
+    """
+    前N档盘口
+    ---
+    - 回测仅在 `Replay.Depth` 时有盘口
+    - 模拟及实盘从交易所获取
+    """
+
+def report() -> Report:
+    """
+    当前策略绩效报告
+    """
+
+def trades() -> List[Trade]:
+    """
+    当前策略成交记录
+    """
+
+def equity_curve() -> List[Tuple[datetime, Decimal]]:
+    """
+    当前策略权益曲线, 每分钟结束时记录
+    """
+
+def import_agg_trades(symbol: str, path: str) -> int:
+    """
+    导入币安 `aggTrades` 历史数据文件到回测数据库, 返回导入数量
+    ---
+    - 支持 `.zip` 压缩包及解压后的 `.csv` 文件
+    - 不能在运行中调用
+    """
+
+def import_depth(symbol: str, path: str) -> int:
+    """
+    导入录制的盘口数据文件到回测数据库, 返回导入数量
+    ---
+    - 支持 `.zip` 压缩包及解压后的文件
+    - 每行一条币安原始消息 : `/fapi/v1/depth` 快照或 `depthUpdate` 增量更新
+    - 不能在运行中调用
+    """
+
+def optimize(
+    strategy: str,
+    param_grid: Dict[str, List[Any]],
+    metric: str = "sharpe",
+    mode: Mode = Mode.Backtest,
+    config: Optional[Config] = None,
+    samples: Optional[int] = None,
+) -> pl.DataFrame:
+    """
+    参数优化
+    ---
+    - `param_grid` 为参数名到候选值列表的映射, 参数覆盖策略模块中的同名变量
+    - 默认网格搜索全部组合, 指定 `samples` 时随机抽取N个组合
+    - 每个组合在独立的Python子进程中回测, 并行数量为CPU核数
+    - 返回按 `metric` 排序的结果, 包含参数及 `Report` 全部指标
+    - 仅支持回测
+    """
+
+def walk_forward(
+    strategy: str,
+    param_grid: Dict[str, List[Any]],
+    in_sample_days: int,
+    out_sample_days: int,
+    metric: str = "sharpe",
+    anchored: bool = False,
+    config: Optional[Config] = None,
+    samples: Optional[int] = None,
+) -> Dict[str, Any]:
+    """
+    前进分析
+    ---
+    - `config` 必须设置 `begin` 和 `end`
+    - 滚动窗口 : 样本内长度固定, 每次向前移动一个样本外长度
+    - 锚定窗口 (`anchored=True`) : 样本内始终从 `begin` 开始
+    - 每个窗口先在样本内按 `metric` 参数优化, 再以最优参数回测随后的样本外区间
+    ---
+    返回
+    - `windows` : 各窗口的区间, 最优参数, 样本内指标 `in_{metric}` 及样本外 `Report` 全部指标
+    - `equity` : 拼接后的样本外权益曲线
+    - `stability` : 各参数的不同取值数量, 最常用取值及其占比
+    - `report` : 拼接后权益曲线的 `Report`, 不含成交统计
+    """
+
+def monte_carlo(
+    method: Resample = Resample.Bootstrap,
+    runs: int = 1000,
+    skip_rate: float = 0.1,
+    ruin: float = 0.5,
+) -> pl.DataFrame:
+    """
+    蒙特卡洛分析
+    ---
+    - 对当前策略的成交记录重采样 `runs` 次, 按每笔扣除手续费后的盈亏重建权益曲线
+    - `skip_rate` 为 `Resample.Skip` 时每笔成交被跳过的概率
+    - 权益任一时刻亏损超过初始资金的 `ruin` 比例视为破产
+    - 返回每次模拟的最终收益率, 最大回撤及是否破产
+    - 摘要写入 `report().monte_carlo`
+    - 需在运行结束后调用
+    """
+
+def run_worker():
+    """
+    参数优化子进程入口, 内部使用
     """
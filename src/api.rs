@@ -1,12 +1,19 @@
 use crate::{
+  book::{Book, OrderBook},
+  engine::Strategy,
   global,
-  models::Order,
-  types::{Mode, OrderStatus},
+  models::{Config, Order, SymbolInfo, Trade},
+  report::Report,
+  types::{MarginMode, Market, Mode, Resample, Side, TimeFrame, Type},
 };
-use anyhow::Result;
-use chrono::{DateTime, Utc};
-use pyo3::{prelude::*, types::PyTuple};
-use rust_decimal::Decimal;
+use anyhow::{ensure, Result};
+use chrono::{DateTime, Duration, Utc};
+use pyo3::{
+  prelude::*,
+  types::{PyDict, PyList, PyTuple},
+};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::collections::BTreeMap;
 
 #[pyfunction]
 #[pyo3(signature = (*args))]
@@ -86,6 +93,30 @@ pub fn account_pnl() -> Decimal {
   global::engine().lock().account.pnl
 }
 
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn account_equity() -> Decimal {
+  global::engine().lock().account.equity()
+}
+
+#[pyfunction]
+#[pyo3(signature = (asset))]
+pub fn account_balance(asset: &str) -> Decimal {
+  global::engine().lock().account.balances.get(asset).cloned().unwrap_or_default()
+}
+
+#[pyfunction]
+#[pyo3(signature = (asset))]
+pub fn account_available_balance(asset: &str) -> Decimal {
+  global::engine().lock().account.available_balances.get(asset).cloned().unwrap_or_default()
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn account_balances() -> BTreeMap<String, Decimal> {
+  global::engine().lock().account.balances.clone()
+}
+
 #[pyfunction]
 #[pyo3(signature = (symbol))]
 pub fn position_long_size(symbol: &str) -> Option<Decimal> {
@@ -116,6 +147,12 @@ pub fn position_long_pnl(symbol: &str) -> Option<Decimal> {
   global::engine().lock().pairs.get(symbol).map(|v| v.long.pnl)
 }
 
+#[pyfunction]
+#[pyo3(signature = (symbol))]
+pub fn position_long_liquidation_price(symbol: &str) -> Option<Decimal> {
+  global::engine().lock().pairs.get(symbol).map(|v| v.long.liquidation_price)
+}
+
 #[pyfunction]
 #[pyo3(signature = (symbol))]
 pub fn position_short_size(symbol: &str) -> Option<Decimal> {
@@ -146,12 +183,30 @@ pub fn position_short_pnl(symbol: &str) -> Option<Decimal> {
   global::engine().lock().pairs.get(symbol).map(|v| v.short.pnl)
 }
 
+#[pyfunction]
+#[pyo3(signature = (symbol))]
+pub fn position_short_liquidation_price(symbol: &str) -> Option<Decimal> {
+  global::engine().lock().pairs.get(symbol).map(|v| v.short.liquidation_price)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol))]
+pub fn pair_market(symbol: &str) -> Option<Market> {
+  global::engine().lock().pairs.get(symbol).map(|v| v.info.market)
+}
+
 #[pyfunction]
 #[pyo3(signature = (symbol))]
 pub fn pair_leverage(symbol: &str) -> Option<Decimal> {
   global::engine().lock().pairs.get(symbol).map(|v| v.leverage)
 }
 
+#[pyfunction]
+#[pyo3(signature = (symbol))]
+pub fn pair_margin_mode(symbol: &str) -> Option<MarginMode> {
+  global::engine().lock().pairs.get(symbol).map(|v| v.margin_mode)
+}
+
 #[pyfunction]
 #[pyo3(signature = (symbol))]
 pub fn pair_margin(symbol: &str) -> Option<Decimal> {
@@ -177,25 +232,7 @@ pub fn pair_open_orders(symbol: &str) -> Vec<Order> {
     .lock()
     .pairs
     .get(symbol)
-    .map(|v| {
-      v.orders
-        .iter()
-        .filter_map(|(_, o)| {
-          if matches!(
-            o.status,
-            OrderStatus::Created |
-              OrderStatus::Submited |
-              OrderStatus::Pending |
-              OrderStatus::Partial
-          ) {
-            Some(o)
-          } else {
-            None
-          }
-        })
-        .cloned()
-        .collect()
-    })
+    .map(|v| v.orders.values().filter(|o| o.is_open()).cloned().collect())
     .unwrap_or_default()
 }
 
@@ -210,6 +247,174 @@ pub fn pair_order_ids(symbol: &str) -> Vec<String> {
     .unwrap_or_default()
 }
 
+#[pyfunction]
+#[pyo3(signature = (symbol, r#type, side, size, price=None, reduce=false))]
+pub fn place_order(
+  symbol: &str,
+  r#type: Type,
+  side: Side,
+  size: Decimal,
+  price: Option<Decimal>,
+  reduce: bool,
+) -> Result<Order> {
+  let order = global::engine().lock().place_order(symbol, r#type, side, size, price, reduce)?;
+  crate::engine::dispatch_events()?;
+  Ok(order)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, id, price=None, size=None))]
+pub fn amend_order(
+  symbol: &str,
+  id: &str,
+  price: Option<Decimal>,
+  size: Option<Decimal>,
+) -> Result<Order> {
+  let order = global::engine().lock().amend_order(symbol, id, price, size)?;
+  crate::engine::dispatch_events()?;
+  Ok(order)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, id))]
+pub fn cancel_order(symbol: &str, id: &str) -> Result<Order> {
+  let order = global::engine().lock().cancel_order(symbol, id)?;
+  crate::engine::dispatch_events()?;
+  Ok(order)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, leverage))]
+pub fn set_leverage(symbol: &str, leverage: Decimal) -> Result<()> {
+  global::engine().lock().set_leverage(symbol, leverage)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, mode))]
+pub fn set_margin_mode(symbol: &str, mode: MarginMode) -> Result<()> {
+  global::engine().lock().set_margin_mode(symbol, mode)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, side, amount))]
+pub fn add_margin(symbol: &str, side: Side, amount: Decimal) -> Result<()> {
+  ensure!(amount.is_sign_positive(), "数量无效 : {amount}");
+  global::engine().lock().adjust_margin(symbol, side, amount)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, side, amount))]
+pub fn reduce_margin(symbol: &str, side: Side, amount: Decimal) -> Result<()> {
+  ensure!(amount.is_sign_positive(), "数量无效 : {amount}");
+  global::engine().lock().adjust_margin(symbol, side, -amount)
+}
+
+#[pyfunction]
+#[pyo3(signature = (reason))]
+pub fn halt(reason: &str) -> Result<()> {
+  let halted = {
+    let engine = global::engine();
+    let mut engine = engine.lock();
+    let halted = engine.halted.is_none();
+    engine.halt(reason)?;
+    halted
+  };
+  crate::engine::dispatch_events()?;
+  if halted {
+    global::strategy_callback().on_stop()?;
+  }
+  Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn resume() {
+  global::engine().lock().resume()
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn is_halted() -> bool {
+  global::engine().lock().halted.is_some()
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol))]
+pub fn symbol_info(symbol: &str) -> Result<Option<SymbolInfo>> {
+  crate::symbol::get(&global::engine().lock().db, symbol)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, price))]
+pub fn round_price(symbol: &str, price: Decimal) -> Result<Decimal> {
+  let info = crate::symbol::get(&global::engine().lock().db, symbol)?;
+  Ok(info.map(|v| v.round_price(price)).unwrap_or(price))
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, size))]
+pub fn round_size(symbol: &str, size: Decimal) -> Result<Decimal> {
+  let info = crate::symbol::get(&global::engine().lock().db, symbol)?;
+  Ok(info.map(|v| v.round_size(size)).unwrap_or(size))
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, cash))]
+pub fn size_by_cash(symbol: &str, cash: Decimal) -> Result<Decimal> {
+  crate::sizing::size_by_cash(&global::engine().lock(), symbol, cash)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, side, stop_distance, risk_pct))]
+pub fn size_by_risk(
+  symbol: &str,
+  side: Side,
+  stop_distance: Decimal,
+  risk_pct: Decimal,
+) -> Result<Decimal> {
+  crate::sizing::size_by_risk(&global::engine().lock(), symbol, side, stop_distance, risk_pct)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, side, target_vol, atr))]
+pub fn size_by_volatility(
+  symbol: &str,
+  side: Side,
+  target_vol: Decimal,
+  atr: Decimal,
+) -> Result<Decimal> {
+  crate::sizing::size_by_volatility(&global::engine().lock(), symbol, side, target_vol, atr)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, win_rate, win_loss_ratio, fraction=Decimal::ONE))]
+pub fn size_by_kelly(
+  symbol: &str,
+  win_rate: Decimal,
+  win_loss_ratio: Decimal,
+  fraction: Decimal,
+) -> Result<Decimal> {
+  crate::sizing::size_by_kelly(&global::engine().lock(), symbol, win_rate, win_loss_ratio, fraction)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, timeframe))]
+pub fn subscribe(symbol: &str, timeframe: TimeFrame) {
+  global::engine().lock().subscribe(symbol, timeframe)
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, timeframe))]
+pub fn unsubscribe(symbol: &str, timeframe: TimeFrame) {
+  global::engine().lock().unsubscribe(symbol, timeframe)
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn strategy_name() -> String {
+  global::engine().lock().name.clone()
+}
+
 #[pyfunction]
 #[pyo3(signature = ())]
 pub fn is_running() -> bool {
@@ -235,10 +440,263 @@ pub fn symbols() -> Vec<String> {
 }
 
 #[pyfunction]
-#[pyo3(signature = (mode, strategy))]
-pub fn run(py: Python, mode: Mode, strategy: &str) -> Result<()> {
+#[pyo3(signature = (symbol, depth=10))]
+pub fn book(py: Python, symbol: &str, depth: usize) -> Result<Option<Book>> {
+  let (mode, book, market, exchange_symbol) = {
+    let engine = global::engine();
+    let engine = engine.lock();
+    let info = engine.pairs.get(symbol).map(|v| v.info.clone()).unwrap_or_default();
+    (
+      engine.mode,
+      engine.books.get(symbol).map(|v| v.to_book(symbol, depth)),
+      info.market,
+      if info.symbol.is_empty() { symbol.to_owned() } else { info.exchange_symbol() },
+    )
+  };
+  match mode {
+    Mode::Backtest => Ok(book),
+    Mode::Sandbox | Mode::Real => {
+      let update = py.allow_threads(|| crate::gateway::depth(market, &exchange_symbol, depth))?;
+      let mut book = OrderBook::default();
+      book.apply(&update);
+      Ok(Some(book.to_book(symbol, depth)))
+    },
+  }
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn report() -> Report {
+  Report::from_context(&global::engine().lock())
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn trades() -> Vec<Trade> {
+  global::engine().lock().trades.clone()
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn equity_curve() -> Vec<(DateTime<Utc>, Decimal)> {
+  global::engine().lock().equity.clone()
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, path))]
+pub fn import_agg_trades(py: Python, symbol: &str, path: &str) -> Result<usize> {
+  py.allow_threads(|| {
+    crate::engine::release()?;
+    let db = crate::helpers::database::open(Mode::Backtest)?;
+    let count = crate::market::import_agg_trades(&db, symbol, path)?;
+    tracing::info!("导入归集成交 : {} {}", symbol, count);
+    Ok(count)
+  })
+}
+
+#[pyfunction]
+#[pyo3(signature = (symbol, path))]
+pub fn import_depth(py: Python, symbol: &str, path: &str) -> Result<usize> {
+  py.allow_threads(|| {
+    crate::engine::release()?;
+    let db = crate::helpers::database::open(Mode::Backtest)?;
+    let count = crate::market::import_depth(&db, symbol, path)?;
+    tracing::info!("导入盘口 : {} {}", symbol, count);
+    Ok(count)
+  })
+}
+
+#[pyfunction]
+#[pyo3(signature = (strategy, param_grid, metric="sharpe", mode=Mode::Backtest, config=None, samples=None))]
+pub fn optimize(
+  py: Python,
+  strategy: &str,
+  param_grid: &Bound<'_, PyDict>,
+  metric: &str,
+  mode: Mode,
+  config: Option<Config>,
+  samples: Option<usize>,
+) -> Result<PyObject> {
+  ensure!(mode == Mode::Backtest, "参数优化仅支持回测");
+  let json = py.import_bound("json")?;
+  let grid = param_grid_json(py, param_grid)?;
+  let python = py.import_bound("sys")?.getattr("executable")?.extract::<String>()?;
+  let config = config.unwrap_or_default();
+  let results = py.allow_threads(|| {
+    crate::optimize::optimize(&python, strategy, &grid, metric, &config, samples)
+  })?;
+
+  let rows = PyList::empty_bound(py);
+  for (i, result) in results.iter().enumerate() {
+    let row = PyDict::new_bound(py);
+    row.set_item("rank", i + 1)?;
+    for (key, value) in result.params.iter() {
+      row.set_item(key, json.call_method1("loads", (value,))?)?;
+    }
+    for (key, value) in result.report.metrics() {
+      row.set_item(key, value)?;
+    }
+    rows.append(row)?;
+  }
+  data_frame(py, rows)
+}
+
+#[pyfunction]
+#[pyo3(signature = (strategy, param_grid, in_sample_days, out_sample_days, metric="sharpe", anchored=false, config=None, samples=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn walk_forward(
+  py: Python,
+  strategy: &str,
+  param_grid: &Bound<'_, PyDict>,
+  in_sample_days: i64,
+  out_sample_days: i64,
+  metric: &str,
+  anchored: bool,
+  config: Option<Config>,
+  samples: Option<usize>,
+) -> Result<PyObject> {
+  let json = py.import_bound("json")?;
+  let grid = param_grid_json(py, param_grid)?;
+  let python = py.import_bound("sys")?.getattr("executable")?.extract::<String>()?;
+  let config = config.unwrap_or_default();
+  let result = py.allow_threads(|| {
+    crate::walk_forward::walk_forward(
+      &python,
+      strategy,
+      &grid,
+      metric,
+      &config,
+      Duration::days(in_sample_days),
+      Duration::days(out_sample_days),
+      anchored,
+      samples,
+    )
+  })?;
+
+  let windows = PyList::empty_bound(py);
+  for (i, v) in result.windows.iter().enumerate() {
+    let row = PyDict::new_bound(py);
+    row.set_item("window", i + 1)?;
+    row.set_item("in_begin", v.window.in_begin)?;
+    row.set_item("in_end", v.window.in_end)?;
+    row.set_item("out_end", v.window.out_end)?;
+    for (key, value) in v.params.iter() {
+      row.set_item(key, json.call_method1("loads", (value,))?)?;
+    }
+    row.set_item(format!("in_{metric}"), v.in_sample.metric(metric))?;
+    for (key, value) in v.out_sample.metrics() {
+      row.set_item(key, value)?;
+    }
+    windows.append(row)?;
+  }
+
+  let equity = PyList::empty_bound(py);
+  for (time, value) in result.equity.iter() {
+    let row = PyDict::new_bound(py);
+    row.set_item("time", time)?;
+    row.set_item("equity", value)?;
+    equity.append(row)?;
+  }
+
+  let stability = PyList::empty_bound(py);
+  for v in result.stability() {
+    let row = PyDict::new_bound(py);
+    row.set_item("param", v.param)?;
+    row.set_item("distinct", v.distinct)?;
+    row.set_item("mode", v.mode)?;
+    row.set_item("mode_share", v.mode_share)?;
+    stability.append(row)?;
+  }
+
+  let output = PyDict::new_bound(py);
+  output.set_item("windows", data_frame(py, windows)?)?;
+  output.set_item("equity", data_frame(py, equity)?)?;
+  output.set_item("stability", data_frame(py, stability)?)?;
+  output.set_item("report", result.report().into_py(py))?;
+  Ok(output.into_any().unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (method=Resample::Bootstrap, runs=1000, skip_rate=0.1, ruin=0.5))]
+pub fn monte_carlo(
+  py: Python,
+  method: Resample,
+  runs: usize,
+  skip_rate: f64,
+  ruin: f64,
+) -> Result<PyObject> {
+  let (cash, trades) = {
+    let engine = global::engine();
+    let engine = engine.lock();
+    (engine.start_equity().to_f64().unwrap_or_default(), engine.cash_trades())
+  };
+  let (samples, summary) = py.allow_threads(|| {
+    crate::monte_carlo::monte_carlo(cash, &trades, method, runs, skip_rate, ruin)
+  })?;
+  global::engine().lock().monte_carlo = Some(summary);
+
+  let rows = PyList::empty_bound(py);
+  for (i, v) in samples.iter().enumerate() {
+    let row = PyDict::new_bound(py);
+    row.set_item("run", i + 1)?;
+    row.set_item("final_return", v.final_return)?;
+    row.set_item("max_drawdown", v.max_drawdown)?;
+    row.set_item("ruined", v.ruined)?;
+    rows.append(row)?;
+  }
+  data_frame(py, rows)
+}
+
+#[pyfunction]
+#[pyo3(signature = ())]
+pub fn run_worker(py: Python) -> Result<()> {
+  py.allow_threads(crate::optimize::run_worker)
+}
+
+/// 参数网格, 候选值序列化为JSON
+fn param_grid_json(
+  py: Python,
+  param_grid: &Bound<'_, PyDict>,
+) -> Result<Vec<(String, Vec<String>)>> {
+  let json = py.import_bound("json")?;
+  let mut grid = vec![];
+  for (key, values) in param_grid.iter() {
+    let values = values
+      .iter()?
+      .map(|v| json.call_method1("dumps", (v?,))?.extract::<String>())
+      .collect::<PyResult<Vec<_>>>()?;
+    grid.push((key.extract::<String>()?, values));
+  }
+  Ok(grid)
+}
+
+/// 转换为 `polars.DataFrame`
+fn data_frame(py: Python, rows: Bound<'_, PyList>) -> Result<PyObject> {
+  Ok(py.import_bound("polars")?.call_method1("DataFrame", (rows,))?.unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (mode, strategy, config=None))]
+pub fn run(py: Python, mode: Mode, strategy: &str, config: Option<Config>) -> Result<()> {
+  let config = config.unwrap_or_default();
+  py.allow_threads(|| {
+    crate::engine::start(mode, vec![Strategy::new(strategy, config.cash)], config, false)?;
+    anyhow::Ok(())
+  })?;
+  Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (mode, strategies, config=None))]
+pub fn run_portfolio(
+  py: Python,
+  mode: Mode,
+  strategies: Vec<(String, Decimal)>,
+  config: Option<Config>,
+) -> Result<()> {
   py.allow_threads(|| {
-    crate::engine::start(mode, strategy)?;
+    let strategies = strategies.iter().map(|(path, cash)| Strategy::new(path, *cash)).collect();
+    crate::engine::start(mode, strategies, config.unwrap_or_default(), false)?;
     anyhow::Ok(())
   })?;
   Ok(())
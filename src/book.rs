@@ -0,0 +1,108 @@
+use crate::models::DepthUpdate;
+use chrono::prelude::*;
+use pyo3::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// 订单簿, 由全量快照及增量更新重建
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+  /// 更新时间
+  pub time: i64,
+  /// 最后更新ID, 0表示尚未收到快照
+  pub update_id: i64,
+  /// 买盘, 价格到数量
+  pub bids: BTreeMap<Decimal, Decimal>,
+  /// 卖盘, 价格到数量
+  pub asks: BTreeMap<Decimal, Decimal>,
+}
+
+impl OrderBook {
+  /// 应用盘口更新, 返回是否生效
+  /// ---
+  /// - 快照替换全部档位
+  /// - 收到快照前及早于当前更新ID的增量更新忽略
+  pub fn apply(&mut self, update: &DepthUpdate) -> bool {
+    if update.snapshot {
+      self.bids.clear();
+      self.asks.clear();
+    } else if self.update_id == 0 || update.update_id <= self.update_id {
+      return false;
+    }
+    for (levels, side) in [(&update.bids, &mut self.bids), (&update.asks, &mut self.asks)] {
+      for (price, size) in levels.iter() {
+        let (Ok(price), Ok(size)) = (Decimal::try_from(*price), Decimal::try_from(*size)) else {
+          continue;
+        };
+        if size.is_zero() {
+          side.remove(&price);
+        } else {
+          side.insert(price, size);
+        }
+      }
+    }
+    self.time = update.time;
+    self.update_id = update.update_id;
+    true
+  }
+
+  /// 价格档位上的挂单数量, 买单在买盘排队, 卖单在卖盘排队
+  pub fn size_at(&self, buy: bool, price: Decimal) -> Decimal {
+    let side = if buy { &self.bids } else { &self.asks };
+    side.get(&price).copied().unwrap_or_default()
+  }
+
+  /// 前N档盘口, 买盘价格从高到低, 卖盘价格从低到高
+  pub fn to_book(&self, symbol: &str, depth: usize) -> Book {
+    Book {
+      symbol: symbol.to_owned(),
+      time: DateTime::from_timestamp_millis(self.time).unwrap_or_default(),
+      bids: self.bids.iter().rev().take(depth).map(|(p, s)| (*p, *s)).collect(),
+      asks: self.asks.iter().take(depth).map(|(p, s)| (*p, *s)).collect(),
+    }
+  }
+}
+
+/// 盘口
+#[pyclass(get_all)]
+#[derive(Debug, Clone)]
+pub struct Book {
+  pub symbol: String,
+  /// 更新时间
+  pub time: DateTime<Utc>,
+  /// 买盘 (价格, 数量), 价格从高到低
+  pub bids: Vec<(Decimal, Decimal)>,
+  /// 卖盘 (价格, 数量), 价格从低到高
+  pub asks: Vec<(Decimal, Decimal)>,
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::models::DepthUpdate;
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let update = |update_id: i64, snapshot: bool, bids: Vec<(f64, f64)>| DepthUpdate {
+      time: update_id,
+      update_id,
+      snapshot,
+      bids,
+      asks: vec![(101.0, 1.0), (102.0, 2.0)],
+    };
+    let mut book = super::OrderBook::default();
+    assert!(!book.apply(&update(1, false, vec![(100.0, 1.0)])));
+    assert!(book.apply(&update(2, true, vec![(100.0, 3.0), (99.0, 4.0)])));
+    assert!(book.apply(&update(3, false, vec![(100.0, 0.0), (99.5, 1.0)])));
+    assert!(!book.apply(&update(3, false, vec![(98.0, 1.0)])));
+
+    let book = book.to_book("BTCUSDT", 2);
+    assert_eq!(
+      book.bids,
+      vec![(Decimal::new(995, 1), Decimal::ONE), (Decimal::from(99), Decimal::from(4))]
+    );
+    assert_eq!(book.asks[0], (Decimal::from(101), Decimal::ONE));
+    Ok(())
+  }
+}
@@ -1,33 +1,1003 @@
 use crate::{
-  global::{set_engine, set_strategy_callback, strategy_callback},
-  helpers::constants::{Environment, BANNER},
-  models::{Context, StrategyCallback},
-  types::Mode,
+  book::OrderBook,
+  global::{
+    clear_engine, current_strategy, engine, gateway, set_current_strategy, set_engine,
+    set_strategy_callback, set_strategy_callbacks, strategy_callback, strategy_count,
+  },
+  helpers::{
+    constants::{Environment, BANNER},
+    date::ms_to_date,
+  },
+  models::{
+    Ack, AggTrade, Candle, Config, Context, DepthUpdate, Event, Order, Pair, StrategyCallback,
+    Subscription, SymbolInfo, Tick,
+  },
+  types::{MarginMode, Market, Mode, OrderStatus, Replay, Side, TimeFrame, Type},
 };
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Result};
+use chrono::{prelude::*, Duration, DurationRound};
 use parking_lot::Mutex;
+use pyo3::prelude::*;
+use rust_decimal::Decimal;
 use std::{
+  collections::{BTreeMap, BTreeSet},
   ops::{Deref, DerefMut},
-  sync::Arc,
+  path::Path,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Once,
+  },
 };
 
-pub struct Engine(Context);
+/// 暂停信号
+static HALT_SIGNAL: AtomicBool = AtomicBool::new(false);
 
+/// 实盘订单同步间隔(秒)
+const SYNC_INTERVAL: u64 = 5;
+
+/// 是否运行中
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// 回放的行情
+enum Replayed {
+  Depth(String, DepthUpdate),
+  Trade(String, AggTrade),
+}
+
+impl Replayed {
+  /// 排序键 : 时间, 盘口更新在前, 更新ID或成交ID, 交易对
+  fn key(&self) -> (i64, u8, i64, String) {
+    match self {
+      Self::Depth(symbol, v) => (v.time, 0, v.update_id, symbol.clone()),
+      Self::Trade(symbol, v) => (v.time, 1, v.id, symbol.clone()),
+    }
+  }
+}
+
+/// 引擎, 各策略上下文共享行情、数据库及时钟
+pub struct Engine {
+  contexts: Vec<Context>,
+}
+
+/// 解引用为当前策略上下文
 impl Deref for Engine {
   type Target = Context;
 
   fn deref(&self) -> &Self::Target {
-    &self.0
+    &self.contexts[current_strategy()]
   }
 }
 
 impl DerefMut for Engine {
   fn deref_mut(&mut self) -> &mut Self::Target {
-    &mut self.0
+    &mut self.contexts[current_strategy()]
+  }
+}
+
+impl Engine {
+  /// 下单
+  /// ---
+  /// 订单创建后先经过交易暂停、交易对规则及风控检查, 未通过则直接拒绝
+  pub fn place_order(
+    &mut self,
+    symbol: &str,
+    r#type: Type,
+    side: Side,
+    size: Decimal,
+    price: Option<Decimal>,
+    reduce: bool,
+  ) -> Result<Order> {
+    let mut order = self.new_order(symbol, r#type, side, size, price, reduce)?;
+
+    let reason = match &self.halted {
+      Some(reason) => Some(format!("交易已暂停 : {reason}")),
+      None => self.check_order(&order).err().map(|e| e.to_string()),
+    };
+    if let Some(reason) = reason {
+      tracing::warn!("订单被风控拒绝 : {} {} {}", symbol, order.id, reason);
+      order.status = OrderStatus::Rejected;
+      order.reason = Some(reason);
+      if let Some(pair) = self.pairs.get_mut(symbol) {
+        pair.orders.insert(order.id.clone(), order.clone());
+      }
+      self.events.push(Event::Order(order.clone()));
+      return Ok(order);
+    }
+    let trade_time = self.trade_time;
+    self.risk.record(trade_time);
+
+    if self.mode == Mode::Backtest && !self.config.latency.is_zero() {
+      return Ok(self.defer_order(order));
+    }
+    self.submit_order(order)
+  }
+
+  /// 回测下单延迟, 订单以 `Submited` 状态等待到达
+  fn defer_order(&mut self, mut order: Order) -> Order {
+    order.status = OrderStatus::Submited;
+    if let Some(pair) = self.pairs.get_mut(&order.symbol) {
+      pair.orders.insert(order.id.clone(), order.clone());
+    }
+    self.events.push(Event::Order(order.clone()));
+    let at = self.clock + self.config.latency.sample(self.config.latency.submit);
+    self.defer(at, Ack::Place(order.symbol.clone(), order.id.clone()));
+    order
+  }
+
+  /// 加入待到达的订单请求, 同一时间按加入顺序
+  fn defer(&mut self, at: DateTime<Utc>, ack: Ack) {
+    let i = self.acks.partition_point(|v| v.0 <= at);
+    self.acks.insert(i, (at, ack));
+  }
+
+  /// 处理到达时间不晚于 `until` 的订单请求
+  /// ---
+  /// - 下单到达时检查资金及持仓后挂单, 可立即成交时按标记价格吃单成交
+  /// - 撤单到达时订单已结束则忽略, 到达前已成交的部分保留
+  fn process_acks(&mut self, until: DateTime<Utc>) -> Result<()> {
+    let count = self.acks.partition_point(|v| v.0 <= until);
+    let acks = self.acks.drain(..count).collect::<Vec<_>>();
+    for (_, ack) in acks {
+      match ack {
+        Ack::Place(symbol, id) => {
+          let order = self.pairs.get(&symbol).and_then(|v| v.orders.get(&id)).cloned();
+          if let Some(order) = order.filter(|v| v.status == OrderStatus::Submited) {
+            crate::matcher::place_order(self, order)?;
+          }
+        },
+        Ack::Cancel(symbol, id) => {
+          let order = self.pairs.get(&symbol).and_then(|v| v.orders.get(&id));
+          if order.is_some_and(|v| v.is_open()) {
+            crate::matcher::cancel_order(self, &symbol, &id)?;
+          }
+        },
+      }
+    }
+    Ok(())
+  }
+
+  /// 下单前检查交易对规则及风控
+  fn check_order(&mut self, order: &Order) -> Result<()> {
+    if let Some(pair) = self.pairs.get(&order.symbol) {
+      ensure!(
+        !(self.mode == Mode::Real && pair.info.market != Market::UsdM),
+        "{}暂不支持实盘交易 : {}",
+        pair.info.market,
+        order.symbol
+      );
+      pair.info.check_order(order)?;
+    }
+    crate::risk::check(self, order)
+  }
+
+  /// 创建订单
+  fn new_order(
+    &self,
+    symbol: &str,
+    r#type: Type,
+    side: Side,
+    size: Decimal,
+    price: Option<Decimal>,
+    reduce: bool,
+  ) -> Result<Order> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    let price = match r#type {
+      Type::Limit => price.ok_or(anyhow!("限价订单必须指定价格"))?,
+      Type::Market => price.unwrap_or(pair.mark_price),
+    };
+    ensure!(size.is_sign_positive() && !size.is_zero(), "数量无效 : {size}");
+    Ok(Order {
+      symbol: symbol.to_owned(),
+      id: crate::helpers::id::gen(),
+      r#type,
+      side,
+      reduce,
+      leverage: pair.leverage,
+      size,
+      price,
+      time: self.trade_time,
+      margin: Decimal::ZERO,
+      deal_size: Decimal::ZERO,
+      deal_price: Decimal::ZERO,
+      deal_fee: Decimal::ZERO,
+      status: OrderStatus::Created,
+      amends: vec![],
+      reason: None,
+      strategy: self.name.clone(),
+      queue: None,
+    })
+  }
+
+  /// 提交订单
+  /// ---
+  /// 实盘先通知 `Submited`, 交易所拒绝时订单为 `Rejected`, 否则同步交易所状态及成交
+  fn submit_order(&mut self, mut order: Order) -> Result<Order> {
+    order.status = OrderStatus::Submited;
+    match self.mode {
+      Mode::Backtest | Mode::Sandbox => crate::matcher::place_order(self, order),
+      Mode::Real => {
+        let (symbol, id) = (order.symbol.clone(), order.id.clone());
+        if let Some(pair) = self.pairs.get_mut(&symbol) {
+          pair.orders.insert(id.clone(), order.clone());
+        }
+        self.events.push(Event::Order(order.clone()));
+        if let Err(e) = gateway().and_then(|v| v.place_order(&order)) {
+          tracing::warn!("交易所拒绝订单 : {} {} {}", symbol, id, e);
+          order.status = OrderStatus::Rejected;
+          order.reason = Some(e.to_string());
+          if let Some(pair) = self.pairs.get_mut(&symbol) {
+            pair.orders.insert(id, order.clone());
+          }
+          self.events.push(Event::Order(order.clone()));
+          return Ok(order);
+        }
+        self.sync_order(&symbol, &id)?;
+        self.refresh_account();
+        self
+          .pairs
+          .get(&symbol)
+          .and_then(|v| v.orders.get(&id))
+          .cloned()
+          .ok_or(anyhow!("订单不存在 : {id}"))
+      },
+    }
+  }
+
+  /// 实盘同步有效订单的状态及成交
+  pub fn sync_orders(&mut self) -> Result<()> {
+    let ids = self
+      .pairs
+      .values()
+      .flat_map(|v| v.orders.values().filter(|o| o.is_open() && o.status != OrderStatus::Created))
+      .map(|o| (o.symbol.clone(), o.id.clone()))
+      .collect::<Vec<_>>();
+    for (symbol, id) in ids {
+      if let Err(e) = self.sync_order(&symbol, &id) {
+        tracing::warn!("同步订单失败 : {} {} {}", symbol, id, e);
+      }
+    }
+    self.refresh_account();
+    Ok(())
+  }
+
+  /// 实盘同步订单状态及成交
+  /// ---
+  /// - 按交易所成交记录补齐本地未记录的成交, 手续费取交易所实际扣除, 折算为结算资产
+  /// - 状态与交易所不一致时更新并产生订单事件
+  fn sync_order(&mut self, symbol: &str, id: &str) -> Result<()> {
+    let gateway = gateway()?;
+    let tx = gateway.query_order(symbol, id)?;
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    let order = pair.orders.get(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+    let settle_asset = pair.info.settle_asset.clone();
+    let recorded = order.deal_size;
+    let executed = Decimal::try_from(tx.executed_qty)?;
+    if executed > recorded {
+      let mut done = Decimal::ZERO;
+      for trade in gateway.user_trades(symbol, tx.order_id)? {
+        let size = done + trade.size - done.max(recorded);
+        done += trade.size;
+        if size <= Decimal::ZERO {
+          continue;
+        }
+        let fee = if trade.commission_asset == settle_asset {
+          trade.commission
+        } else {
+          let price = self.asset_price(&settle_asset);
+          if price.is_zero() {
+            Decimal::ZERO
+          } else {
+            trade.commission * self.asset_price(&trade.commission_asset) / price
+          }
+        };
+        let time = DateTime::from_timestamp_millis(trade.time).unwrap_or(self.trade_time);
+        crate::matcher::settle(self, symbol, id, trade.price, size, fee, trade.maker, time, None)?;
+      }
+    }
+
+    // 成交记录未齐时保持当前状态, 下次同步补齐
+    let status = crate::gateway::order_status(&tx.status);
+    let ctx: &mut Context = self;
+    if let Some(order) = ctx.pairs.get_mut(symbol).and_then(|v| v.orders.get_mut(id)) {
+      if order.status != status && order.deal_size >= executed {
+        order.status = status;
+        if !order.is_open() {
+          order.margin = Decimal::ZERO;
+        }
+        ctx.events.push(Event::Order(order.clone()));
+      }
+    }
+    Ok(())
+  }
+
+  /// 修改订单
+  /// ---
+  /// 修改后的订单按下单相同的交易对规则及风控检查, 未通过时报错, 原订单不变
+  pub fn amend_order(
+    &mut self,
+    symbol: &str,
+    id: &str,
+    price: Option<Decimal>,
+    size: Option<Decimal>,
+  ) -> Result<Order> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    let mut amended = pair.orders.get(id).cloned().ok_or(anyhow!("订单不存在 : {id}"))?;
+    amended.amend(self.trade_time, price, size)?;
+    if let Some(reason) = &self.halted {
+      bail!("交易已暂停 : {reason}");
+    }
+    self.check_order(&amended)?;
+
+    match self.mode {
+      Mode::Backtest | Mode::Sandbox => crate::matcher::amend_order(self, symbol, id, price, size),
+      Mode::Real => {
+        let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+        gateway()?.amend_order(&pair.orders[id], amended.price, amended.size)?;
+        amended.margin =
+          pair.info.notional(amended.price, amended.remain_size()) / amended.leverage;
+        if let Some(order) = self.pairs.get_mut(symbol).and_then(|v| v.orders.get_mut(id)) {
+          *order = amended.clone();
+        }
+        self.events.push(Event::Order(amended));
+        self.sync_order(symbol, id)?;
+        self.refresh_account();
+        self
+          .pairs
+          .get(symbol)
+          .and_then(|v| v.orders.get(id))
+          .cloned()
+          .ok_or(anyhow!("订单不存在 : {id}"))
+      },
+    }
+  }
+
+  /// 撤单
+  /// ---
+  /// 回测设置延迟时, 撤单请求到达前订单仍可能成交, 返回当前订单
+  pub fn cancel_order(&mut self, symbol: &str, id: &str) -> Result<Order> {
+    match self.mode {
+      Mode::Backtest if !self.config.latency.is_zero() => {
+        let order = self
+          .pairs
+          .get(symbol)
+          .and_then(|v| v.orders.get(id))
+          .cloned()
+          .ok_or(anyhow!("订单不存在 : {id}"))?;
+        ensure!(order.is_open(), "订单已结束 : {id}");
+        let at = self.clock + self.config.latency.sample(self.config.latency.cancel);
+        self.defer(at, Ack::Cancel(symbol.to_owned(), id.to_owned()));
+        Ok(order)
+      },
+      Mode::Backtest | Mode::Sandbox => crate::matcher::cancel_order(self, symbol, id),
+      Mode::Real => {
+        let order = self
+          .pairs
+          .get_mut(symbol)
+          .and_then(|v| v.orders.get_mut(id))
+          .ok_or(anyhow!("订单不存在 : {id}"))?;
+        ensure!(order.is_open(), "订单已结束 : {id}");
+        gateway()?.cancel_order(order)?;
+        self.sync_order(symbol, id)?;
+        self.refresh_account();
+        self
+          .pairs
+          .get(symbol)
+          .and_then(|v| v.orders.get(id))
+          .cloned()
+          .ok_or(anyhow!("订单不存在 : {id}"))
+      },
+    }
+  }
+
+  /// 撤销交易对所有有效订单
+  /// ---
+  /// 仅撤销当前策略的订单, 实盘逐个撤单, 不影响同一交易对上其他策略的订单
+  pub fn cancel_all_orders(&mut self, symbol: &str) -> Result<()> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    let ids =
+      pair.orders.values().filter(|o| o.is_open()).map(|o| o.id.clone()).collect::<Vec<_>>();
+    if ids.is_empty() {
+      return Ok(());
+    }
+    match self.mode {
+      Mode::Backtest | Mode::Sandbox => {
+        for id in ids {
+          crate::matcher::cancel_order(self, symbol, &id)?;
+        }
+      },
+      Mode::Real => {
+        let gateway = gateway()?;
+        for id in ids {
+          let order = &self.pairs[symbol].orders[&id];
+          if let Err(e) = gateway.cancel_order(order) {
+            tracing::warn!("撤单失败 : {} {} {}", symbol, id, e);
+          }
+          self.sync_order(symbol, &id)?;
+        }
+        self.refresh_account();
+      },
+    }
+    Ok(())
+  }
+
+  /// 修改杠杆倍数
+  /// ---
+  /// - 不能超过交易对最大杠杆, 及持仓与挂单名义价值所在分层的最大杠杆
+  /// - 按新杠杆重算仓位及挂单保证金, 保留逐仓追加的保证金, 可用保证金为负或仓位触及强平价格时拒绝
+  pub fn set_leverage(&mut self, symbol: &str, leverage: Decimal) -> Result<()> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    ensure!(!pair.is_spot(), "现货不支持杠杆 : {symbol}");
+    ensure!(leverage >= Decimal::ONE && leverage.fract().is_zero(), "杠杆倍数无效 : {leverage}");
+    if pair.leverage == leverage {
+      return Ok(());
+    }
+    let info = &pair.info;
+    if !info.max_leverage.is_zero() {
+      ensure!(
+        leverage <= info.max_leverage,
+        "杠杆倍数超过最大杠杆 : {leverage} > {}",
+        info.max_leverage
+      );
+    }
+    let notional = info.notional(pair.mark_price, pair.long.size + pair.short.size)
+      + pair
+        .orders
+        .values()
+        .filter(|o| o.is_open() && !o.reduce)
+        .map(|o| info.notional(o.price, o.remain_size()))
+        .sum::<Decimal>();
+    let max = info.bracket_leverage(notional);
+    ensure!(
+      max.is_zero() || leverage <= max,
+      "杠杆倍数超过名义价值分层最大杠杆 : {leverage} > {max}, 名义价值 {notional}"
+    );
+
+    let backup = pair.clone();
+    self.apply_leverage(symbol, leverage);
+    let pair = &self.pairs[symbol];
+    let available = self.available(pair);
+    let breached = if available.is_sign_negative() {
+      Some(format!("可用资金不足 : {available}"))
+    } else if pair.long.is_liquidated(pair.mark_price) || pair.short.is_liquidated(pair.mark_price)
+    {
+      Some("仓位将被强平".to_owned())
+    } else {
+      None
+    };
+    let result = match breached {
+      Some(reason) => Err(anyhow!("不能修改杠杆倍数 : {reason}")),
+      None if self.mode == Mode::Real => {
+        if pair.info.market == Market::UsdM {
+          gateway().and_then(|v| v.set_leverage(symbol, leverage))
+        } else {
+          Err(anyhow!("{}暂不支持实盘交易 : {symbol}", pair.info.market))
+        }
+      },
+      None => Ok(()),
+    };
+    if result.is_err() {
+      self.pairs.insert(symbol.to_owned(), backup);
+      self.refresh_account();
+    }
+    result
+  }
+
+  /// 按杠杆倍数重算仓位及挂单保证金
+  /// ---
+  /// 仓位保证金按新杠杆重算初始保证金, 保留逐仓追加的保证金
+  fn apply_leverage(&mut self, symbol: &str, leverage: Decimal) {
+    if let Some(pair) = self.pairs.get_mut(symbol) {
+      pair.leverage = leverage;
+      for position in [&mut pair.long, &mut pair.short] {
+        let notional = pair.info.notional(position.price, position.size);
+        let extra = position.margin - notional / position.leverage;
+        position.leverage = leverage;
+        position.margin = notional / leverage + extra;
+      }
+      for order in pair.orders.values_mut().filter(|o| o.is_open() && !o.reduce) {
+        order.leverage = leverage;
+        order.margin = pair.info.notional(order.price, order.remain_size()) / leverage;
+      }
+    }
+    self.refresh_account();
+  }
+
+  /// 修改保证金模式
+  /// ---
+  /// 仅合约, 且交易对无持仓及有效订单
+  pub fn set_margin_mode(&mut self, symbol: &str, mode: MarginMode) -> Result<()> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    ensure!(!pair.is_spot(), "现货不支持保证金模式 : {symbol}");
+    if pair.margin_mode == mode {
+      return Ok(());
+    }
+    ensure!(pair.is_idle(), "存在持仓或有效订单, 不能修改保证金模式 : {symbol}");
+    if self.mode == Mode::Real {
+      ensure!(pair.info.market == Market::UsdM, "{}暂不支持实盘交易 : {symbol}", pair.info.market);
+      gateway()?.set_margin_mode(symbol, mode)?;
+    }
+    if let Some(pair) = self.pairs.get_mut(symbol) {
+      pair.margin_mode = mode;
+    }
+    self.refresh_account();
+    Ok(())
+  }
+
+  /// 调整逐仓保证金, 正数增加, 负数减少
+  /// ---
+  /// - 增加不能超过交易对可用保证金
+  /// - 减少后仓位保证金不能低于按当前标记价格计算的初始保证金
+  pub fn adjust_margin(&mut self, symbol: &str, side: Side, amount: Decimal) -> Result<()> {
+    let pair = self.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+    ensure!(pair.is_isolated(), "仅逐仓可调整保证金 : {symbol}");
+    ensure!(!amount.is_zero(), "数量无效 : {amount}");
+    let position = match side {
+      Side::Long => &pair.long,
+      Side::Short => &pair.short,
+    };
+    ensure!(!position.size.is_zero(), "无持仓 : {symbol}");
+    if amount.is_sign_positive() {
+      let available = self.available(pair);
+      ensure!(amount <= available, "可用资金不足 : {amount} > {available}");
+    } else {
+      let initial = pair.info.notional(pair.mark_price, position.size) / position.leverage;
+      let max = position.margin + position.pnl.min(Decimal::ZERO) - initial;
+      ensure!(-amount <= max, "可减少保证金不足 : {} > {max}", -amount);
+    }
+    if self.mode == Mode::Real {
+      ensure!(pair.info.market == Market::UsdM, "{}暂不支持实盘交易 : {symbol}", pair.info.market);
+      gateway()?.adjust_margin(symbol, side, amount)?;
+    }
+    if let Some(pair) = self.pairs.get_mut(symbol) {
+      match side {
+        Side::Long => pair.long.margin += amount,
+        Side::Short => pair.short.margin += amount,
+      }
+    }
+    self.refresh_account();
+    Ok(())
+  }
+
+  /// 暂停交易
+  /// ---
+  /// - 撤销所有交易对的有效订单
+  /// - 以减仓市价单平掉所有多空仓位
+  /// - 停止运行, 恢复前无法下单
+  pub fn halt(&mut self, reason: &str) -> Result<()> {
+    if self.halted.is_some() {
+      return Ok(());
+    }
+    tracing::error!("交易暂停 : {reason}");
+    self.halted = Some(reason.to_owned());
+
+    let symbols = self.pairs.keys().cloned().collect::<Vec<_>>();
+    for symbol in symbols {
+      self.cancel_all_orders(&symbol)?;
+      self.close_positions(&symbol)?;
+    }
+
+    self.running = false;
+    Ok(())
+  }
+
+  /// 以减仓市价单平掉交易对多空仓位, 不经过风控检查
+  fn close_positions(&mut self, symbol: &str) -> Result<()> {
+    for side in [Side::Long, Side::Short] {
+      let size = match self.pairs.get(symbol) {
+        Some(pair) if side == Side::Long => pair.long.size,
+        Some(pair) => pair.short.size,
+        None => Decimal::ZERO,
+      };
+      if !size.is_zero() {
+        let order = self.new_order(symbol, Type::Market, side, size, None, true)?;
+        self.submit_order(order)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// 调整交易对池, 返回新增及移除的交易对
+  /// ---
+  /// - 新入选的交易对创建 `Pair`
+  /// - 已下线的交易对撤销订单并平仓
+  /// - 落选的交易对无持仓且无有效订单时移除, 否则保留至下次调整
+  fn rebalance(&mut self, selected: &[SymbolInfo]) -> Result<(Vec<String>, Vec<String>)> {
+    let now = self.trade_time.timestamp_millis();
+
+    let mut added = vec![];
+    for info in selected.iter() {
+      if !self.pairs.contains_key(&info.symbol) {
+        added.push(info.symbol.clone());
+        let pair = Pair::new(info.clone(), &self.config);
+        self.pairs.insert(info.symbol.clone(), pair);
+      }
+    }
+
+    let mut removed = vec![];
+    let mut symbols = self
+      .pairs
+      .keys()
+      .filter(|v| !selected.iter().any(|s| &s.symbol == *v))
+      .cloned()
+      .collect::<Vec<_>>();
+    symbols.sort();
+    for symbol in symbols {
+      if self.pairs.get(&symbol).is_some_and(|v| !v.info.is_listed(now)) {
+        tracing::warn!("交易对已下线 : {symbol}");
+        self.cancel_all_orders(&symbol)?;
+        self.close_positions(&symbol)?;
+      }
+      if self.pairs.get(&symbol).is_some_and(|v| v.is_idle()) {
+        self.pairs.remove(&symbol);
+        self.candles.remove(&symbol);
+        removed.push(symbol);
+      }
+    }
+    added.sort();
+
+    if !added.is_empty() || !removed.is_empty() {
+      tracing::info!("交易对池调整 : 新增 {:?} 移除 {:?}", added, removed);
+    }
+    self.refresh_account();
+    Ok((added, removed))
+  }
+
+  /// 各策略交易对, 去重排序
+  fn symbols(&self) -> BTreeSet<String> {
+    self.contexts.iter().flat_map(|v| v.pairs.keys().cloned()).collect()
+  }
+
+  /// 加载回测K线, 各策略共享, 标记价格更新为开盘价
+  fn load_candles(&mut self, time: DateTime<Utc>) -> Result<()> {
+    let time = time.timestamp_millis();
+    let mut candles = BTreeMap::new();
+    for symbol in self.symbols() {
+      if let Some(candle) = crate::market::candle(&self.db, &symbol, TimeFrame::Minute, time)? {
+        candles.insert(symbol, candle);
+      }
+    }
+    for ctx in self.contexts.iter_mut() {
+      ctx.candles = candles
+        .iter()
+        .filter(|(k, _)| ctx.pairs.contains_key(*k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+      for (symbol, candle) in ctx.candles.iter() {
+        if let Some(pair) = ctx.pairs.get_mut(symbol) {
+          pair.mark_price = Decimal::try_from(candle.open)?;
+        }
+      }
+      ctx.refresh_account();
+    }
+    Ok(())
+  }
+
+  /// 加载回测当前分钟的回放行情, 各策略共享
+  /// ---
+  /// 按时间排序, 同一时间盘口更新在成交之前
+  fn load_replay(&self, time: DateTime<Utc>) -> Result<Vec<Replayed>> {
+    let begin = time.timestamp_millis();
+    let end = (time + Duration::minutes(1)).timestamp_millis();
+    let mut items = vec![];
+    for symbol in self.symbols() {
+      if self.config.replay == Replay::Depth {
+        for update in crate::market::depth_updates(&self.db, &symbol, begin, end)? {
+          items.push(Replayed::Depth(symbol.clone(), update));
+        }
+      }
+      for trade in crate::market::agg_trades(&self.db, &symbol, begin, end)? {
+        items.push(Replayed::Trade(symbol.clone(), trade));
+      }
+    }
+    items.sort_by_key(|v| v.key());
+    Ok(items)
+  }
+
+  /// 重建新增交易对的订单簿, 从最近快照开始应用增量更新, 并移除已退出交易对的订单簿
+  fn init_books(&mut self, time: DateTime<Utc>) -> Result<()> {
+    let time = time.timestamp_millis();
+    let mut books = BTreeMap::new();
+    for symbol in self.symbols() {
+      if self
+        .contexts
+        .iter()
+        .all(|v| !v.pairs.contains_key(&symbol) || v.books.contains_key(&symbol))
+      {
+        continue;
+      }
+      let mut book = OrderBook::default();
+      if let Some(snapshot) = crate::market::depth_snapshot(&self.db, &symbol, time)? {
+        for update in crate::market::depth_updates(&self.db, &symbol, snapshot.time, time)? {
+          book.apply(&update);
+        }
+      }
+      books.insert(symbol, book);
+    }
+    for ctx in self.contexts.iter_mut() {
+      let ctx: &mut Context = ctx;
+      ctx.books.retain(|k, _| ctx.pairs.contains_key(k));
+      for (symbol, book) in books.iter() {
+        if ctx.pairs.contains_key(symbol) && !ctx.books.contains_key(symbol) {
+          ctx.books.insert(symbol.clone(), book.clone());
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// 各策略应用盘口更新并更新排队位置
+  fn apply_depth(&mut self, symbol: &str, update: &DepthUpdate) {
+    for ctx in self.contexts.iter_mut() {
+      if ctx.books.get_mut(symbol).is_some_and(|v| v.apply(update)) {
+        crate::matcher::update_queues(ctx, symbol);
+      }
+    }
+  }
+
+  /// 按归集成交撮合挂单并合成当前分钟K线, 返回行情事件
+  /// ---
+  /// 交易时间更新为成交时间, 非当前策略交易对时返回 `None`
+  fn match_agg_trade(&mut self, symbol: &str, trade: &AggTrade) -> Result<Option<Tick>> {
+    if !self.pairs.contains_key(symbol) {
+      return Ok(None);
+    }
+    let time = ms_to_date(trade.time)?;
+    self.trade_time = time;
+    self.clock = time;
+    self.process_acks(time)?;
+    match self.candles.get_mut(symbol) {
+      Some(candle) => candle.push_agg_trade(trade),
+      None => {
+        let begin = trade.time - trade.time.rem_euclid(60_000);
+        self.candles.insert(symbol.to_owned(), Candle::from_agg_trade(begin, trade));
+      },
+    }
+    crate::matcher::match_agg_trade(self, symbol, trade)?;
+    Ok(Some(Tick {
+      symbol: symbol.to_owned(),
+      time,
+      price: Decimal::try_from(trade.price)?,
+      size: Decimal::try_from(trade.size)?,
+    }))
+  }
+
+  /// 拉取实时K线并保存, 各策略共享
+  fn fetch_candles(&mut self, time: DateTime<Utc>) -> Result<()> {
+    let time = time.timestamp_millis();
+    let mut candles = BTreeMap::new();
+    for symbol in self.symbols() {
+      let info = self
+        .contexts
+        .iter()
+        .find_map(|v| v.pairs.get(&symbol))
+        .map(|v| v.info.clone())
+        .unwrap_or_default();
+      let items = if info.symbol.is_empty() {
+        crate::gateway::klines(Market::UsdM, &symbol, TimeFrame::Minute, time, 1)?
+      } else {
+        crate::gateway::klines(info.market, &info.exchange_symbol(), TimeFrame::Minute, time, 1)?
+      };
+      crate::market::save_candles(&self.db, &symbol, TimeFrame::Minute, &items)?;
+      if let Some(candle) = items.into_iter().find(|v| v.time == time) {
+        candles.insert(symbol, candle);
+      }
+    }
+    for ctx in self.contexts.iter_mut() {
+      ctx.candles = candles
+        .iter()
+        .filter(|(k, _)| ctx.pairs.contains_key(*k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    }
+    Ok(())
+  }
+
+  /// 订阅K线, 收盘时触发 `on_bar`
+  pub fn subscribe(&mut self, symbol: &str, timeframe: TimeFrame) {
+    if !self.subscriptions.iter().any(|v| v.symbol == symbol && v.timeframe == timeframe) {
+      self.subscriptions.push(Subscription { symbol: symbol.to_owned(), timeframe, candle: None });
+    }
+  }
+
+  /// 取消订阅K线
+  pub fn unsubscribe(&mut self, symbol: &str, timeframe: TimeFrame) {
+    self.subscriptions.retain(|v| v.symbol != symbol || v.timeframe != timeframe);
+  }
+
+  /// 按当前分钟K线合成订阅周期K线, 返回已收盘的K线
+  /// ---
+  /// 首次合成时从数据库补齐当前周期已有的分钟K线
+  fn close_bars(&mut self, time: DateTime<Utc>) -> Result<Vec<(String, TimeFrame, Candle)>> {
+    let ctx: &mut Context = self;
+    let next = time + Duration::minutes(1);
+    let mut closed = vec![];
+    for sub in ctx.subscriptions.iter_mut() {
+      let begin = sub.timeframe.bar_begin(time);
+      if sub.candle.is_none() && begin < time {
+        let candles = crate::market::candles(
+          &ctx.db,
+          &sub.symbol,
+          TimeFrame::Minute,
+          begin.timestamp_millis(),
+          time.timestamp_millis(),
+        )?;
+        for candle in candles.iter() {
+          merge(&mut sub.candle, begin, candle);
+        }
+      }
+      if let Some(candle) = ctx.candles.get(&sub.symbol) {
+        merge(&mut sub.candle, begin, candle);
+      }
+      if sub.timeframe.bar_end(time) <= next {
+        if let Some(candle) = sub.candle.take() {
+          closed.push((sub.symbol.clone(), sub.timeframe, candle));
+        }
+      }
+    }
+    Ok(closed)
+  }
+
+  /// 当前K线行情事件, 按交易对排序
+  fn ticks(&self) -> Result<Vec<Tick>> {
+    let time = self.trade_time + Duration::minutes(1);
+    let mut ticks = self
+      .candles
+      .iter()
+      .map(|(symbol, candle)| {
+        Ok(Tick {
+          symbol: symbol.clone(),
+          time,
+          price: Decimal::try_from(candle.close)?,
+          size: Decimal::try_from(candle.volume)?,
+        })
+      })
+      .collect::<Result<Vec<_>>>()?;
+    ticks.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    Ok(ticks)
+  }
+
+  /// 按当前K线撮合挂单
+  fn match_candles(&mut self) -> Result<()> {
+    let candles = self.candles.clone();
+    for (symbol, candle) in candles.iter() {
+      match self.mode {
+        Mode::Backtest | Mode::Sandbox => crate::matcher::match_candle(self, symbol, candle)?,
+        Mode::Real => {
+          if let Some(pair) = self.pairs.get_mut(symbol) {
+            pair.mark_price = Decimal::try_from(candle.close)?;
+          }
+        },
+      }
+    }
+    self.refresh_account();
+    Ok(())
+  }
+
+  /// 分钟结束时撮合及处理订单请求
+  /// ---
+  /// - 本分钟内没有到达的订单请求时, 按K线撮合已接受的挂单
+  /// - 有订单请求到达时, 按到达时间将K线内价格路径分段 : 先撮合到达前的路径,
+  ///   标记价格更新为到达时的路径价格后处理请求, 再撮合之后的路径
+  /// - 在途撤单在路径上先于成交到达时生效, 本分钟内到达的下单参与之后路径的撮合
+  fn close_minute(&mut self, next: DateTime<Utc>, replay: bool) -> Result<()> {
+    let begin = next - Duration::minutes(1);
+    let inflight = self.acks.first().is_some_and(|v| v.0 > begin && v.0 <= next);
+    if replay || self.mode == Mode::Real || !inflight {
+      if !replay {
+        self.match_candles()?;
+      }
+      self.process_acks(next)?;
+      self.clock = next;
+      return Ok(());
+    }
+
+    let candles = self.candles.clone();
+    let mut paths = vec![];
+    for (symbol, candle) in candles.iter() {
+      paths.push((symbol, crate::matcher::intrabar_path(self, symbol, candle)?));
+    }
+    let mut from = Decimal::ZERO;
+    while let Some(at) = self.acks.first().map(|v| v.0).filter(|v| *v <= next) {
+      let elapsed = Decimal::from((at - begin).num_milliseconds().clamp(0, 60_000));
+      let to = elapsed / Decimal::from(60_000);
+      for (symbol, path) in paths.iter() {
+        crate::matcher::match_path(self, symbol, path, from, to)?;
+        if let Some(pair) = self.pairs.get_mut(*symbol) {
+          pair.mark_price = crate::matcher::path_price(&path.1, to);
+        }
+      }
+      self.refresh_account();
+      self.clock = at.max(begin);
+      self.process_acks(at)?;
+      from = to;
+    }
+    for (symbol, path) in paths.iter() {
+      crate::matcher::match_path(self, symbol, path, from, Decimal::ONE)?;
+      crate::matcher::close_candle(self, symbol, &candles[*symbol])?;
+    }
+    self.refresh_account();
+    self.clock = next;
+    Ok(())
   }
+
+  /// 恢复交易
+  pub fn resume(&mut self) {
+    if let Some(reason) = self.halted.take() {
+      tracing::info!("交易恢复 : {reason}");
+      let equity = self.account.equity();
+      self.risk.reset_peak(equity);
+      self.running = true;
+    }
+  }
+
+  /// 检查交易暂停条件, 返回触发原因
+  /// ---
+  /// - 账户回撤超过 `RiskLimits::max_drawdown`
+  /// - `Config::halt_file` 文件存在
+  /// - 收到 `SIGUSR1` 信号
+  fn halt_trigger(&mut self, signal: bool) -> Option<String> {
+    if let Some(max) = self.config.risk.max_drawdown {
+      let equity = self.account.equity();
+      let drawdown = self.risk.drawdown(equity);
+      if drawdown >= max {
+        return Some(format!("账户回撤超限 : {drawdown} >= {max}"));
+      }
+    }
+    if let Some(file) = &self.config.halt_file {
+      if Path::new(file).exists() {
+        return Some(format!("暂停文件存在 : {file}"));
+      }
+    }
+    if signal {
+      return Some("收到暂停信号".to_owned());
+    }
+    None
+  }
+}
+
+/// 策略
+pub struct Strategy {
+  /// 策略文件
+  pub path: String,
+  /// 子账户资金
+  pub cash: Decimal,
+  /// 注入策略模块的参数, 值为JSON
+  pub params: Vec<(String, String)>,
+}
+
+impl Strategy {
+  pub fn new(path: &str, cash: Decimal) -> Self {
+    Self { path: path.to_owned(), cash, params: vec![] }
+  }
+}
+
+/// 运行策略
+/// ---
+/// - 多个策略共享行情、数据库及时钟, 每个策略拥有独立的子账户
+/// - 每次运行创建新的引擎, 同一进程可重复运行, 但不能同时运行
+/// - `read_only` 时只读打开数据库, 供并行回测使用
+pub fn start(mode: Mode, strategies: Vec<Strategy>, config: Config, read_only: bool) -> Result<()> {
+  ensure!(!ACTIVE.swap(true, Ordering::SeqCst), "已有策略运行中");
+  let result = run(mode, strategies, config, read_only);
+  ACTIVE.store(false, Ordering::SeqCst);
+  result
 }
 
-pub fn start(mode: Mode, strategy: &str) -> Result<()> {
+/// 释放上次运行的引擎, 运行中时报错
+pub fn release() -> Result<()> {
+  ensure!(!ACTIVE.load(Ordering::SeqCst), "已有策略运行中");
+  clear_engine();
+  Ok(())
+}
+
+fn run(mode: Mode, strategies: Vec<Strategy>, config: Config, read_only: bool) -> Result<()> {
+  ensure!(!strategies.is_empty(), "未指定策略");
+
+  // 释放上次运行的引擎及数据库
+  clear_engine();
+  set_current_strategy(0);
+  HALT_SIGNAL.store(false, Ordering::SeqCst);
+
   // 显示Banner
   if Environment::ShowBanner.as_bool(true) {
     println!("{}", BANNER);
@@ -39,27 +1009,507 @@ pub fn start(mode: Mode, strategy: &str) -> Result<()> {
   // 初始化rayon
   crate::helpers::runtime::init_rayon()?;
 
+  // 数据库
+  let db = if read_only {
+    crate::helpers::database::open_read_only(mode)?
+  } else {
+    crate::helpers::database::open(mode)?
+  };
+
+  // 随机种子
+  let seed = match (config.seed, mode) {
+    (Some(seed), _) => seed,
+    (None, Mode::Backtest) => 0,
+    (None, Mode::Sandbox | Mode::Real) => rand::random(),
+  };
+  crate::helpers::random::seed(seed);
+  Python::with_gil(|py| py.import_bound("random")?.call_method1("seed", (seed,)).map(|_| ()))?;
+  tracing::info!("随机种子 : {}", seed);
+
+  // 交易对规则
+  let count = match (&config.symbol_file, mode) {
+    (Some(file), _) => crate::symbol::load_file(&db, file)?,
+    (None, Mode::Sandbox | Mode::Real) => crate::symbol::fetch(&db)?,
+    (None, Mode::Backtest) => crate::symbol::all(&db)?.len(),
+  };
+  tracing::info!("交易对规则 : {}", count);
+
   // 上下文
-  let ctx = Context {
-    db: crate::helpers::database::open(mode)?,
-    running: false,
-    trade_time: Default::default(),
-    benchmark: Default::default(),
-    account: Default::default(),
-    pairs: Default::default(),
+  let trade_time = match mode {
+    Mode::Backtest => config.begin.ok_or(anyhow!("回测必须指定开始时间"))?,
+    Mode::Sandbox | Mode::Real => Utc::now(),
   };
+  // 初始钱包余额按资金比例分配到各策略, 资金合计为0时平均分配
+  let total = strategies.iter().map(|v| v.cash).sum::<Decimal>();
+  let mut contexts = vec![];
+  for strategy in strategies.iter() {
+    let cash = strategy.cash;
+    let share =
+      if total.is_zero() { Decimal::ONE / Decimal::from(strategies.len()) } else { cash / total };
+    let balances = config.balances.iter().map(|(k, v)| (k.clone(), v * share)).collect();
+    let name = Path::new(&strategy.path)
+      .file_stem()
+      .map(|v| v.to_string_lossy().to_string())
+      .unwrap_or(strategy.path.clone());
+    ensure!(!contexts.iter().any(|v: &Context| v.name == name), "策略名称重复 : {name}");
+    let config = Config { cash, balances, ..config.clone() };
+    contexts.push(Context::new(&name, mode, db.clone(), config, trade_time));
+  }
 
   // 引擎
-  set_engine(Arc::new(Mutex::new(Engine(ctx))))?;
+  set_engine(Arc::new(Mutex::new(Engine { contexts })))?;
 
   // 策略回调
-  set_strategy_callback(Arc::new(StrategyCallback::new(strategy)?))?;
+  set_strategy_callbacks(
+    strategies
+      .iter()
+      .map(|v| Ok(Arc::new(StrategyCallback::new(&v.path, &v.params)?)))
+      .collect::<Result<Vec<_>>>()?,
+  )?;
+
+  // 监听暂停信号
+  listen_halt_signal();
 
   // 策略初始化
-  strategy_callback().on_init()?;
+  each_strategy(|| {
+    strategy_callback().on_init()?;
+    dispatch_events()
+  })?;
+
+  // 检查交易暂停
+  check_halt()?;
 
-  // 策略停止运行
-  strategy_callback().on_stop()?;
+  // 运行
+  each_strategy(|| {
+    let engine = engine();
+    let mut engine = engine.lock();
+    if engine.halted.is_none() {
+      engine.running = true;
+    }
+    Ok(())
+  })?;
+  match mode {
+    Mode::Backtest => backtest()?,
+    Mode::Sandbox | Mode::Real => live()?,
+  }
+
+  // 策略停止运行, 暂停交易时已通知
+  each_strategy(|| {
+    let (halted, name, cash, pnl) = {
+      let engine = engine();
+      let engine = engine.lock();
+      (engine.halted.is_some(), engine.name.clone(), engine.account.cash, engine.account.pnl)
+    };
+    tracing::info!("策略 {} : 资金 {} 浮动盈亏 {}", name, cash, pnl);
+    if !halted {
+      strategy_callback().on_stop()?;
+    }
+    Ok(())
+  })?;
 
   Ok(())
 }
+
+/// 依次切换到每个策略执行
+fn each_strategy<F>(mut f: F) -> Result<()>
+where
+  F: FnMut() -> Result<()>,
+{
+  let current = current_strategy();
+  for i in 0..strategy_count() {
+    set_current_strategy(i);
+    if let Err(e) = f() {
+      set_current_strategy(current);
+      return Err(e);
+    }
+  }
+  set_current_strategy(current);
+  Ok(())
+}
+
+/// 依次切换到每个运行中的策略执行
+fn each_running_strategy<F>(mut f: F) -> Result<()>
+where
+  F: FnMut() -> Result<()>,
+{
+  each_strategy(|| if is_running() { f() } else { Ok(()) })
+}
+
+/// 策略热重载
+/// ---
+/// - 策略文件修改后重新编译, 保留模块变量, 替换策略回调后执行 `on_reload`, 不再执行 `on_init`
+/// - 编译或 `on_reload` 失败时回滚到原策略回调, 已修改的模块变量及共享对象不会恢复
+/// - 引擎中的订单及仓位不受影响
+fn reload_strategy() -> Result<()> {
+  let current = strategy_callback();
+  if !current.is_modified() {
+    return Ok(());
+  }
+  let reloaded = match current.reload() {
+    Ok(v) => Arc::new(v),
+    Err(e) => {
+      tracing::error!("策略重载编译失败, 继续使用原策略 : {}", e);
+      return Ok(());
+    },
+  };
+  set_strategy_callback(reloaded.clone())?;
+  match reloaded.on_reload().and_then(|_| dispatch_events()) {
+    Ok(_) => tracing::info!("策略已重载"),
+    Err(e) => {
+      tracing::error!("策略重载失败, 回滚到原策略 : {}", e);
+      set_strategy_callback(current)?;
+    },
+  }
+  Ok(())
+}
+
+/// 按产生顺序通知当前策略订单及成交事件
+/// ---
+/// 在释放引擎锁后调用, 策略回调中产生的新事件在本次一并通知
+pub fn dispatch_events() -> Result<()> {
+  loop {
+    let events = std::mem::take(&mut engine().lock().events);
+    if events.is_empty() {
+      return Ok(());
+    }
+    let callback = strategy_callback();
+    for event in events {
+      match event {
+        Event::Order(order) => callback.on_order(order)?,
+        Event::Trade(trade) => callback.on_trade(trade)?,
+      }
+    }
+  }
+}
+
+/// 检查各策略交易暂停条件, 触发时暂停交易并通知策略停止运行
+pub fn check_halt() -> Result<()> {
+  let signal = HALT_SIGNAL.swap(false, Ordering::SeqCst);
+  each_strategy(|| {
+    let halted = {
+      let engine = engine();
+      let mut engine = engine.lock();
+      if engine.halted.is_some() {
+        return Ok(());
+      }
+      match engine.halt_trigger(signal) {
+        Some(reason) => {
+          engine.halt(&reason)?;
+          true
+        },
+        None => false,
+      }
+    };
+    dispatch_events()?;
+    if halted {
+      strategy_callback().on_stop()?;
+    }
+    Ok(())
+  })
+}
+
+/// 合并分钟K线, 开盘时间取周期开盘时间
+fn merge(bar: &mut Option<Candle>, begin: DateTime<Utc>, candle: &Candle) {
+  match bar {
+    Some(bar) => bar.merge(candle),
+    None => *bar = Some(Candle { time: begin.timestamp_millis(), ..candle.clone() }),
+  }
+}
+
+/// 当前策略是否运行中
+fn is_running() -> bool {
+  engine().lock().running
+}
+
+/// 是否有策略运行中
+fn any_running() -> bool {
+  engine().lock().contexts.iter().any(|v| v.running)
+}
+
+/// 回测, 按分钟遍历 `[begin, end)`
+fn backtest() -> Result<()> {
+  let (begin, end) = {
+    let engine = engine();
+    let engine = engine.lock();
+    let begin = engine.config.begin.ok_or(anyhow!("回测必须指定开始时间"))?;
+    (begin, engine.config.end.unwrap_or(Utc::now()))
+  };
+  ensure!(begin < end, "回测时间无效 : {begin} >= {end}");
+  let mut time = begin.duration_trunc(Duration::minutes(1))?;
+  let mut first = true;
+  while time < end && any_running() {
+    begin_minute(time, first)?;
+    end_minute(time)?;
+    time += Duration::minutes(1);
+    first = false;
+  }
+  Ok(())
+}
+
+/// 模拟及实盘, 按分钟边界运行, 每分钟开始前检查策略热重载
+/// ---
+/// 实盘等待期间每 `SYNC_INTERVAL` 秒同步一次有效订单, 状态变化及成交即时通知策略
+fn live() -> Result<()> {
+  let real = engine().lock().mode == Mode::Real;
+  let mut time = Utc::now().duration_trunc(Duration::minutes(1))?;
+  let mut first = true;
+  while any_running() {
+    each_running_strategy(reload_strategy)?;
+    begin_minute(time, first)?;
+    let next = time + Duration::minutes(1);
+    while let Ok(wait) = (next - Utc::now()).to_std() {
+      if !real {
+        std::thread::sleep(wait);
+        break;
+      }
+      std::thread::sleep(wait.min(std::time::Duration::from_secs(SYNC_INTERVAL)));
+      each_running_strategy(|| {
+        engine().lock().sync_orders()?;
+        dispatch_events()
+      })?;
+    }
+    end_minute(time)?;
+    time = next;
+    first = false;
+  }
+  Ok(())
+}
+
+/// 分钟开始
+/// ---
+/// - 首次运行及每日开始时调整交易对池, 各策略使用相同的交易对池
+/// - 回测加载当前分钟K线, 逐笔回放时当前分钟K线在回放中合成
+/// - 按盘口回放时重建新增交易对的订单簿
+fn begin_minute(time: DateTime<Utc>, first: bool) -> Result<()> {
+  let day_begin = time.hour() == 0 && time.minute() == 0;
+  let changes = {
+    let engine = engine();
+    let mut engine = engine.lock();
+    for ctx in engine.contexts.iter_mut() {
+      ctx.trade_time = time;
+      ctx.clock = time;
+      let equity = ctx.account.equity();
+      ctx.risk.roll_day(time, equity);
+    }
+    let mut changes = vec![];
+    if first || day_begin {
+      let selected = crate::universe::select(&engine, time)?;
+      let current = current_strategy();
+      for i in 0..engine.contexts.len() {
+        set_current_strategy(i);
+        changes.push(engine.rebalance(&selected)?);
+      }
+      set_current_strategy(current);
+    }
+    match (engine.mode, engine.config.replay) {
+      (Mode::Backtest, Replay::Candle) => engine.load_candles(time)?,
+      (Mode::Backtest, replay) => {
+        for ctx in engine.contexts.iter_mut() {
+          ctx.candles.clear();
+        }
+        if replay == Replay::Depth {
+          engine.init_books(time)?;
+        }
+      },
+      (Mode::Sandbox | Mode::Real, _) => {},
+    }
+    changes
+  };
+
+  each_running_strategy(|| {
+    dispatch_events()?;
+    let callback = strategy_callback();
+    if let Some((added, removed)) = changes.get(current_strategy()).cloned() {
+      if !added.is_empty() || !removed.is_empty() {
+        callback.on_universe_change(added, removed)?;
+      }
+    }
+    if day_begin {
+      callback.on_day_begin()?;
+    }
+    if time.minute() == 0 {
+      callback.on_hour_begin()?;
+    }
+    callback.on_minute_begin()
+  })
+}
+
+/// 分钟结束
+/// ---
+/// - 按当前分钟K线撮合挂单并合成订阅K线后通知策略, 并检查交易暂停条件
+/// - 逐笔回放时, 逐笔撮合并通知 `on_tick`
+fn end_minute(time: DateTime<Utc>) -> Result<()> {
+  let replay = {
+    let engine = engine();
+    let mut engine = engine.lock();
+    if engine.mode != Mode::Backtest {
+      engine.fetch_candles(time)?;
+    }
+    engine.mode == Mode::Backtest && engine.config.replay != Replay::Candle
+  };
+  if replay {
+    replay_market(time)?;
+  }
+
+  each_running_strategy(|| {
+    let (ticks, bars) = {
+      let engine = engine();
+      let mut engine = engine.lock();
+      let next = time + Duration::minutes(1);
+      engine.close_minute(next, replay)?;
+      engine.record_equity(time + Duration::minutes(1));
+      (if replay { None } else { Some(engine.ticks()?) }, engine.close_bars(time)?)
+    };
+    dispatch_events()?;
+
+    let callback = strategy_callback();
+    if let Some(ticks) = ticks {
+      callback.on_tick(ticks)?;
+    }
+    for (symbol, timeframe, candle) in bars {
+      callback.on_bar(&symbol, timeframe, candle)?;
+    }
+    callback.on_minute_end()?;
+    if time.minute() == 59 {
+      callback.on_hour_end()?;
+    }
+    if time.hour() == 23 && time.minute() == 59 {
+      callback.on_day_end()?;
+    }
+    Ok(())
+  })?;
+
+  check_halt()
+}
+
+/// 逐笔回放当前分钟的行情
+/// ---
+/// - 盘口更新应用到各策略的订单簿, 不通知策略
+/// - 每笔成交依次在各运行中的策略撮合后通知 `on_tick`
+/// - 回放结束后交易时间恢复为分钟开始时间
+fn replay_market(time: DateTime<Utc>) -> Result<()> {
+  let items = engine().lock().load_replay(time)?;
+  for item in items.iter() {
+    let (symbol, trade) = match item {
+      Replayed::Depth(symbol, update) => {
+        engine().lock().apply_depth(symbol, update);
+        continue;
+      },
+      Replayed::Trade(symbol, trade) => (symbol, trade),
+    };
+    each_running_strategy(|| {
+      let tick = engine().lock().match_agg_trade(symbol, trade)?;
+      dispatch_events()?;
+      if let Some(tick) = tick {
+        strategy_callback().on_tick(vec![tick])?;
+      }
+      Ok(())
+    })?;
+  }
+  for ctx in engine().lock().contexts.iter_mut() {
+    ctx.trade_time = time;
+  }
+  Ok(())
+}
+
+/// 监听暂停信号 `SIGUSR1`, 仅首次运行时注册
+fn listen_halt_signal() {
+  #[cfg(unix)]
+  static LISTEN: Once = Once::new();
+  #[cfg(unix)]
+  LISTEN.call_once(|| {
+    crate::helpers::runtime::tokio().spawn(async {
+      use tokio::signal::unix::{signal, SignalKind};
+      match signal(SignalKind::user_defined1()) {
+        Ok(mut s) => {
+          while s.recv().await.is_some() {
+            HALT_SIGNAL.store(true, Ordering::SeqCst);
+          }
+        },
+        Err(e) => tracing::warn!("监听暂停信号失败 : {}", e),
+      }
+    });
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Engine;
+  use crate::{
+    helpers::date::str_to_date,
+    models::{Ack, Candle, Config, Context, Pair, SymbolInfo},
+    types::{MarginMode, Mode, OrderStatus, Side, Type},
+  };
+  use anyhow::Result;
+  use chrono::Duration;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let d = |v: &str| v.parse::<Decimal>().unwrap();
+    let time = str_to_date("202402070537")?;
+    let db = crate::helpers::database::open_temp()?;
+    let ctx = Context::new("test", Mode::Backtest, db, Config::default(), time);
+    let mut engine = Engine { contexts: vec![ctx] };
+    let info = SymbolInfo { symbol: "BTCUSDT".to_owned(), ..Default::default() };
+    let mut pair = Pair::new(info, &engine.config);
+    pair.mark_price = d("105");
+    engine.pairs.insert("BTCUSDT".to_owned(), pair);
+
+    // 路径 104-105-99-101 各线段20秒, 约第36.7秒跌至100 :
+    // 1毫秒后到达的撤单先于成交生效, 50秒后到达的撤单晚于成交被忽略
+    let mut ids = vec![];
+    for (i, latency) in [1, 50_000].into_iter().enumerate() {
+      let mut order =
+        engine.new_order("BTCUSDT", Type::Limit, Side::Long, d("1"), Some(d("100")), false)?;
+      order.time += Duration::seconds(i as i64);
+      let order = crate::matcher::place_order(&mut engine, order)?;
+      assert_eq!(order.status, OrderStatus::Pending);
+      let at = time + Duration::milliseconds(latency);
+      engine.defer(at, Ack::Cancel("BTCUSDT".to_owned(), order.id.clone()));
+      ids.push(order.id);
+    }
+    let candle = Candle {
+      time: time.timestamp_millis(),
+      open: 104.0,
+      high: 105.0,
+      low: 99.0,
+      close: 101.0,
+      volume: 10.0,
+      amount: 1000.0,
+      trades: 10,
+      taker_volume: 5.0,
+      taker_amount: 500.0,
+    };
+    engine.candles.insert("BTCUSDT".to_owned(), candle);
+    engine.close_minute(time + Duration::minutes(1), false)?;
+    let orders = &engine.pairs["BTCUSDT"].orders;
+    assert_eq!(orders[&ids[0]].status, OrderStatus::Canceled);
+    assert_eq!(orders[&ids[0]].deal_size, Decimal::ZERO);
+    assert_eq!(orders[&ids[1]].status, OrderStatus::Completed);
+    assert_eq!(orders[&ids[1]].deal_size, d("1"));
+    assert_eq!(engine.pairs["BTCUSDT"].mark_price, d("101"));
+
+    // 逐仓追加保证金后修改杠杆, 保留追加部分
+    let config =
+      Config { leverage: d("10"), margin_mode: MarginMode::Isolated, ..Default::default() };
+    let db = crate::helpers::database::open_temp()?;
+    let ctx = Context::new("test", Mode::Backtest, db, config, time);
+    let mut engine = Engine { contexts: vec![ctx] };
+    let info = SymbolInfo { symbol: "ETHUSDT".to_owned(), ..Default::default() };
+    let mut pair = Pair::new(info, &engine.config);
+    pair.mark_price = d("100");
+    engine.pairs.insert("ETHUSDT".to_owned(), pair);
+    let order = engine.new_order("ETHUSDT", Type::Market, Side::Long, d("10"), None, false)?;
+    let order = crate::matcher::place_order(&mut engine, order)?;
+    assert_eq!(order.status, OrderStatus::Completed);
+    assert_eq!(engine.pairs["ETHUSDT"].long.margin, d("100"));
+    engine.adjust_margin("ETHUSDT", Side::Long, d("50"))?;
+    engine.set_leverage("ETHUSDT", d("5"))?;
+    assert_eq!(engine.pairs["ETHUSDT"].long.margin, d("250"));
+    engine.set_leverage("ETHUSDT", d("20"))?;
+    assert_eq!(engine.pairs["ETHUSDT"].long.margin, d("100"));
+    assert!(engine.acks.is_empty());
+    Ok(())
+  }
+}
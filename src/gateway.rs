@@ -0,0 +1,306 @@
+use crate::{
+  helpers::{constants::Environment, runtime::tokio},
+  models::{Candle, DepthUpdate, Order},
+  types::{MarginMode, Market, OrderStatus, Side, TimeFrame, Type},
+};
+use anyhow::{anyhow, bail, Result};
+use binance::{
+  futures::rest_model::{CanceledOrder, Order as ExchangeOrder, Transaction},
+  rest_model::OrderStatus as BinanceOrderStatus,
+};
+use reqwest::{header::HeaderValue, Method};
+use ring::hmac;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// U本位合约接口地址
+const HOST: &str = "https://fapi.binance.com";
+
+/// 币本位合约接口地址
+const COIN_HOST: &str = "https://dapi.binance.com";
+
+/// 现货接口地址
+const SPOT_HOST: &str = "https://api.binance.com";
+
+/// 请求有效时间窗口(毫秒)
+const RECV_WINDOW: u64 = 5000;
+
+/// 账户成交记录
+#[derive(Debug, Clone)]
+pub struct UserTrade {
+  /// 成交ID
+  pub id: u64,
+  /// 成交时间
+  pub time: i64,
+  pub price: Decimal,
+  pub size: Decimal,
+  /// 手续费
+  pub commission: Decimal,
+  /// 手续费资产
+  pub commission_asset: String,
+  /// 是否挂单成交
+  pub maker: bool,
+}
+
+/// 币安U本位合约网关
+pub struct Gateway {
+  api_key: String,
+  secret_key: String,
+  client: reqwest::Client,
+}
+
+impl Gateway {
+  pub fn new() -> Result<Self> {
+    let api_key = Environment::ApiKey.value().ok_or(anyhow!("未配置 {}", Environment::ApiKey))?;
+    let secret_key =
+      Environment::ApiSecret.value().ok_or(anyhow!("未配置 {}", Environment::ApiSecret))?;
+    Ok(Self { api_key, secret_key, client: reqwest::Client::new() })
+  }
+
+  /// 签名请求
+  async fn signed<T>(
+    &self,
+    method: Method,
+    endpoint: &str,
+    params: Vec<(&str, String)>,
+  ) -> Result<T>
+  where
+    T: DeserializeOwned,
+  {
+    let query = binance::util::build_signed_request(params, RECV_WINDOW)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, self.secret_key.as_bytes());
+    let signature = hex::encode(hmac::sign(&key, query.as_bytes()).as_ref());
+    let url = format!("{HOST}{endpoint}?{query}&signature={signature}");
+    let resp = self
+      .client
+      .request(method, url)
+      .header("X-MBX-APIKEY", HeaderValue::from_str(&self.api_key)?)
+      .send()
+      .await?;
+    if !resp.status().is_success() {
+      bail!("请求失败 : {} {}", resp.status(), resp.text().await.unwrap_or_default());
+    }
+    Ok(resp.json::<T>().await?)
+  }
+
+  /// 各交易对杠杆分层, (名义价值上限, 最大杠杆倍数), 按名义价值升序
+  pub fn leverage_brackets(&self) -> Result<HashMap<String, Vec<(Decimal, Decimal)>>> {
+    let data: Value =
+      tokio().block_on(self.signed(Method::GET, "/fapi/v1/leverageBracket", vec![]))?;
+    let mut items = HashMap::new();
+    for item in data.as_array().into_iter().flatten() {
+      let Some(symbol) = item["symbol"].as_str() else {
+        continue;
+      };
+      let mut brackets = item["brackets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| {
+          let cap = Decimal::try_from(v["notionalCap"].as_f64()?).ok()?;
+          Some((cap, Decimal::from(v["initialLeverage"].as_i64()?)))
+        })
+        .collect::<Vec<_>>();
+      brackets.sort_by_key(|v| v.0);
+      items.insert(symbol.to_owned(), brackets);
+    }
+    Ok(items)
+  }
+
+  /// 修改杠杆倍数
+  pub fn set_leverage(&self, symbol: &str, leverage: Decimal) -> Result<()> {
+    let params =
+      vec![("symbol", symbol.to_owned()), ("leverage", leverage.normalize().to_string())];
+    tokio().block_on(self.signed::<Value>(Method::POST, "/fapi/v1/leverage", params))?;
+    Ok(())
+  }
+
+  /// 下单
+  pub fn place_order(&self, order: &Order) -> Result<Transaction> {
+    let mut params = vec![
+      ("symbol", order.symbol.clone()),
+      ("newClientOrderId", order.id.clone()),
+      ("side", if order.is_buy() { "BUY" } else { "SELL" }.to_owned()),
+      ("positionSide", order.side.as_ref().to_uppercase()),
+      ("type", order.r#type.as_ref().to_uppercase()),
+      ("quantity", order.size.normalize().to_string()),
+    ];
+    if order.r#type == Type::Limit {
+      params.push(("timeInForce", "GTC".to_owned()));
+      params.push(("price", order.price.normalize().to_string()));
+    }
+    tokio().block_on(self.signed(Method::POST, "/fapi/v1/order", params))
+  }
+
+  /// 修改订单
+  pub fn amend_order(&self, order: &Order, price: Decimal, size: Decimal) -> Result<Transaction> {
+    let params = vec![
+      ("symbol", order.symbol.clone()),
+      ("origClientOrderId", order.id.clone()),
+      ("side", if order.is_buy() { "BUY" } else { "SELL" }.to_owned()),
+      ("quantity", size.normalize().to_string()),
+      ("price", price.normalize().to_string()),
+    ];
+    tokio().block_on(self.signed(Method::PUT, "/fapi/v1/order", params))
+  }
+
+  /// 撤单
+  pub fn cancel_order(&self, order: &Order) -> Result<CanceledOrder> {
+    let params = vec![("symbol", order.symbol.clone()), ("origClientOrderId", order.id.clone())];
+    tokio().block_on(self.signed(Method::DELETE, "/fapi/v1/order", params))
+  }
+
+  /// 查询订单
+  pub fn query_order(&self, symbol: &str, id: &str) -> Result<ExchangeOrder> {
+    let params = vec![("symbol", symbol.to_owned()), ("origClientOrderId", id.to_owned())];
+    tokio().block_on(self.signed(Method::GET, "/fapi/v1/order", params))
+  }
+
+  /// 订单成交记录, 按成交ID升序
+  pub fn user_trades(&self, symbol: &str, order_id: u64) -> Result<Vec<UserTrade>> {
+    let params = vec![("symbol", symbol.to_owned()), ("orderId", order_id.to_string())];
+    let data: Value = tokio().block_on(self.signed(Method::GET, "/fapi/v1/userTrades", params))?;
+    let f = |v: &Value| v.as_str().and_then(|v| v.parse::<Decimal>().ok()).unwrap_or_default();
+    let mut items = data
+      .as_array()
+      .into_iter()
+      .flatten()
+      .map(|v| UserTrade {
+        id: v["id"].as_u64().unwrap_or_default(),
+        time: v["time"].as_i64().unwrap_or_default(),
+        price: f(&v["price"]),
+        size: f(&v["qty"]),
+        commission: f(&v["commission"]),
+        commission_asset: v["commissionAsset"].as_str().unwrap_or_default().to_owned(),
+        maker: v["maker"].as_bool().unwrap_or_default(),
+      })
+      .collect::<Vec<_>>();
+    items.sort_by_key(|v| v.id);
+    Ok(items)
+  }
+
+  /// 修改保证金模式
+  pub fn set_margin_mode(&self, symbol: &str, mode: MarginMode) -> Result<()> {
+    let mode = match mode {
+      MarginMode::Cross => "CROSSED",
+      MarginMode::Isolated => "ISOLATED",
+    };
+    let params = vec![("symbol", symbol.to_owned()), ("marginType", mode.to_owned())];
+    tokio().block_on(self.signed::<Value>(Method::POST, "/fapi/v1/marginType", params))?;
+    Ok(())
+  }
+
+  /// 调整逐仓保证金, 正数增加, 负数减少
+  pub fn adjust_margin(&self, symbol: &str, side: Side, amount: Decimal) -> Result<()> {
+    let params = vec![
+      ("symbol", symbol.to_owned()),
+      ("positionSide", side.as_ref().to_uppercase()),
+      ("amount", amount.abs().normalize().to_string()),
+      ("type", if amount.is_sign_negative() { "2" } else { "1" }.to_owned()),
+    ];
+    tokio().block_on(self.signed::<Value>(Method::POST, "/fapi/v1/positionMargin", params))?;
+    Ok(())
+  }
+}
+
+/// 市场公开接口地址前缀
+fn endpoint(market: Market) -> String {
+  match market {
+    Market::UsdM => format!("{HOST}/fapi/v1"),
+    Market::CoinM => format!("{COIN_HOST}/dapi/v1"),
+    Market::Spot => format!("{SPOT_HOST}/api/v3"),
+  }
+}
+
+/// 交易规则 `exchangeInfo`
+pub fn exchange_info(market: Market) -> Result<Value> {
+  get(&format!("{}/exchangeInfo", endpoint(market)))
+}
+
+/// 各交易对24小时成交额, 按交易所交易对名称
+/// ---
+/// 币本位合约没有计价成交额, 按基础资产成交量及加权均价折算
+pub fn quote_volumes(market: Market) -> Result<HashMap<String, f64>> {
+  let data = get(&format!("{}/ticker/24hr", endpoint(market)))?;
+  let f = |v: &Value| v.as_str().and_then(|v| v.parse::<f64>().ok());
+  let mut items = HashMap::new();
+  for item in data.as_array().into_iter().flatten() {
+    let volume = match market {
+      Market::CoinM => f(&item["baseVolume"]).zip(f(&item["weightedAvgPrice"])).map(|(a, b)| a * b),
+      Market::UsdM | Market::Spot => f(&item["quoteVolume"]),
+    };
+    if let (Some(symbol), Some(volume)) = (item["symbol"].as_str(), volume) {
+      items.insert(symbol.to_owned(), volume);
+    }
+  }
+  Ok(items)
+}
+
+/// K线, 从开始时间起最多 `limit` 根
+/// ---
+/// 币本位合约成交额为基础资产成交量, 按收盘价折算为计价成交额
+pub fn klines(
+  market: Market,
+  symbol: &str,
+  timeframe: TimeFrame,
+  begin: i64,
+  limit: usize,
+) -> Result<Vec<Candle>> {
+  let data = get(&format!(
+    "{}/klines?symbol={symbol}&interval={timeframe}&startTime={begin}&limit={limit}",
+    endpoint(market)
+  ))?;
+  let f = |v: &Value| v.as_str().and_then(|v| v.parse::<f64>().ok()).unwrap_or_default();
+  let mut items = vec![];
+  for item in data.as_array().into_iter().flatten() {
+    let scale = if market == Market::CoinM { f(&item[4]) } else { 1.0 };
+    items.push(Candle {
+      time: item[0].as_i64().unwrap_or_default(),
+      open: f(&item[1]),
+      high: f(&item[2]),
+      low: f(&item[3]),
+      close: f(&item[4]),
+      volume: f(&item[5]),
+      amount: f(&item[7]) * scale,
+      trades: item[8].as_i64().unwrap_or_default(),
+      taker_volume: f(&item[9]),
+      taker_amount: f(&item[10]) * scale,
+    });
+  }
+  Ok(items)
+}
+
+/// 盘口快照, 档位数量取不小于 `limit` 的交易所支持值
+pub fn depth(market: Market, symbol: &str, limit: usize) -> Result<DepthUpdate> {
+  let limit = [5, 10, 20, 50, 100, 500, 1000].into_iter().find(|v| *v >= limit).unwrap_or(1000);
+  let mut data = get(&format!("{}/depth?symbol={symbol}&limit={limit}", endpoint(market)))?;
+  // 现货快照没有时间, 取本地时间
+  if data["T"].is_null() {
+    data["T"] = Value::from(crate::helpers::date::now_ms());
+  }
+  crate::market::parse_depth(&data)
+}
+
+/// 公开接口请求
+fn get(url: &str) -> Result<Value> {
+  tokio().block_on(async {
+    let resp = reqwest::get(url).await?;
+    if !resp.status().is_success() {
+      bail!("请求失败 : {} {}", resp.status(), resp.text().await.unwrap_or_default());
+    }
+    Ok(resp.json::<Value>().await?)
+  })
+}
+
+/// 交易所订单状态转换
+pub fn order_status(status: &BinanceOrderStatus) -> OrderStatus {
+  match status {
+    BinanceOrderStatus::New | BinanceOrderStatus::PendingCancel => OrderStatus::Pending,
+    BinanceOrderStatus::PartiallyFilled | BinanceOrderStatus::Trade => OrderStatus::Partial,
+    BinanceOrderStatus::Filled => OrderStatus::Completed,
+    BinanceOrderStatus::Rejected => OrderStatus::Rejected,
+    _ => OrderStatus::Canceled,
+  }
+}
@@ -1,27 +1,67 @@
-use crate::{engine::Engine, models::StrategyCallback};
+use crate::{engine::Engine, gateway::Gateway, models::StrategyCallback};
 use anyhow::{anyhow, Result};
 use once_cell::sync::OnceCell;
-use parking_lot::Mutex;
-use std::sync::Arc;
+use parking_lot::{Mutex, RwLock};
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc,
+};
 
-static ENGINE: OnceCell<Arc<Mutex<Engine>>> = OnceCell::new();
+/// 当前引擎, 每次运行重新创建, 运行结束后保留至下次运行
+static ENGINE: RwLock<Option<Arc<Mutex<Engine>>>> = RwLock::new(None);
 
 pub fn set_engine(engine: Arc<Mutex<Engine>>) -> Result<()> {
-  ENGINE.set(engine).map_err(|_| anyhow!("设置引擎失败"))?;
+  *ENGINE.write() = Some(engine);
   Ok(())
 }
 
+/// 释放上次运行的引擎
+pub fn clear_engine() {
+  ENGINE.write().take();
+}
+
 pub fn engine() -> Arc<Mutex<Engine>> {
-  ENGINE.get().cloned().unwrap()
+  ENGINE.read().clone().expect("引擎未运行")
 }
 
-static STRATEGY_CALLBACK: OnceCell<Arc<StrategyCallback>> = OnceCell::new();
+static STRATEGY_CALLBACKS: RwLock<Vec<Arc<StrategyCallback>>> = RwLock::new(vec![]);
+
+/// 当前策略序号
+static CURRENT_STRATEGY: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_strategy_callbacks(scs: Vec<Arc<StrategyCallback>>) -> Result<()> {
+  *STRATEGY_CALLBACKS.write() = scs;
+  Ok(())
+}
 
+/// 替换当前策略回调, 热重载时使用
 pub fn set_strategy_callback(sc: Arc<StrategyCallback>) -> Result<()> {
-  STRATEGY_CALLBACK.set(sc).map_err(|_| anyhow!("设置策略回调失败"))?;
+  let mut scs = STRATEGY_CALLBACKS.write();
+  let current = current_strategy();
+  *scs.get_mut(current).ok_or(anyhow!("策略不存在 : {current}"))? = sc;
   Ok(())
 }
 
+/// 当前策略回调
 pub fn strategy_callback() -> Arc<StrategyCallback> {
-  STRATEGY_CALLBACK.get().cloned().unwrap()
+  STRATEGY_CALLBACKS.read()[current_strategy()].clone()
+}
+
+/// 策略数量
+pub fn strategy_count() -> usize {
+  STRATEGY_CALLBACKS.read().len()
+}
+
+pub fn current_strategy() -> usize {
+  CURRENT_STRATEGY.load(Ordering::SeqCst)
+}
+
+pub fn set_current_strategy(index: usize) {
+  CURRENT_STRATEGY.store(index, Ordering::SeqCst);
+}
+
+static GATEWAY: OnceCell<Gateway> = OnceCell::new();
+
+pub fn gateway() -> Result<&'static Gateway> {
+  GATEWAY.get_or_try_init(Gateway::new)
 }
@@ -15,6 +15,12 @@ pub enum Environment {
   /// 显示banner, 默认开启
   #[strum(to_string = "SHOW_BANNER")]
   ShowBanner,
+  /// 交易所API Key, 实盘必须
+  #[strum(to_string = "API_KEY")]
+  ApiKey,
+  /// 交易所API Secret, 实盘必须
+  #[strum(to_string = "API_SECRET")]
+  ApiSecret,
 }
 
 impl Environment {
@@ -45,6 +51,12 @@ impl Environment {
   }
 }
 
+/// 账户资金资产, `Config::cash` 以此计
+pub const CASH_ASSET: &str = "USDT";
+
+/// 稳定币, 没有交易对报价时按1估值
+pub const STABLECOINS: [&str; 4] = ["USDT", "USDC", "FDUSD", "USD"];
+
 /// banner
 pub const BANNER: &str = "
 ██╗    ██╗██╗   ██╗██╗  ██╗ ██████╗ ███╗   ██╗ ██████╗         
@@ -4,10 +4,12 @@ use parking_lot::RwLock;
 use rocksdb::{Direction, IteratorMode, Options, WriteBatch, DB};
 use std::{
   ops::{Deref, DerefMut},
+  path::PathBuf,
   sync::Arc,
 };
 
-pub struct Database(Arc<RwLock<DB>>);
+#[derive(Clone)]
+pub struct Database(Arc<RwLock<DB>>, bool);
 
 impl Deref for Database {
   type Target = Arc<RwLock<DB>>;
@@ -28,6 +30,10 @@ impl Database {
   where
     T: AsRef<str>,
   {
+    // 只读时不创建表, 读取不存在的表返回空
+    if self.1 {
+      return Ok(());
+    }
     let mut db = self.write();
     if db.cf_handle(table.as_ref()).is_none() {
       db.create_cf(table.as_ref(), &Options::default())?;
@@ -43,7 +49,9 @@ impl Database {
   {
     self.init_table(&table)?;
     let db = self.read();
-    let table = db.cf_handle(table.as_ref()).ok_or(anyhow!("table not found"))?;
+    let Some(table) = db.cf_handle(table.as_ref()) else {
+      return Ok(None);
+    };
     let key = bincode::encode_to_vec(key, bincode::config::standard().with_big_endian())?;
     let val = db.get_cf(table, key)?;
     match val {
@@ -55,7 +63,7 @@ impl Database {
     }
   }
 
-  pub fn get_range<T, K, V1, V2, I>(&self, table: T, begin: K, end: K) -> Result<Vec<(V1, V2)>>
+  pub fn get_range<T, K, V1, V2>(&self, table: T, begin: K, end: K) -> Result<Vec<(V1, V2)>>
   where
     T: AsRef<str>,
     K: bincode::Encode,
@@ -64,7 +72,9 @@ impl Database {
   {
     self.init_table(&table)?;
     let db = self.read();
-    let table = db.cf_handle(table.as_ref()).ok_or(anyhow!("table not found"))?;
+    let Some(table) = db.cf_handle(table.as_ref()) else {
+      return Ok(vec![]);
+    };
     let begin = bincode::encode_to_vec(begin, bincode::config::standard().with_big_endian())?;
     let end = bincode::encode_to_vec(end, bincode::config::standard().with_big_endian())?;
     let iter = db.iterator_cf(table, IteratorMode::From(&begin, Direction::Forward));
@@ -86,6 +96,30 @@ impl Database {
     Ok(items)
   }
 
+  pub fn get_all<T, K, V>(&self, table: T) -> Result<Vec<(K, V)>>
+  where
+    T: AsRef<str>,
+    K: bincode::Decode,
+    V: bincode::Decode,
+  {
+    self.init_table(&table)?;
+    let db = self.read();
+    let Some(table) = db.cf_handle(table.as_ref()) else {
+      return Ok(vec![]);
+    };
+    let iter = db.iterator_cf(table, IteratorMode::Start);
+
+    let mut items = vec![];
+    for item in iter {
+      let (key, val) = item?;
+      let (key, _) =
+        bincode::decode_from_slice(&key, bincode::config::standard().with_big_endian())?;
+      let (val, _) = bincode::decode_from_slice(&val, bincode::config::standard())?;
+      items.push((key, val));
+    }
+    Ok(items)
+  }
+
   pub fn set<T, K, V>(&self, table: T, key: K, val: V) -> Result<()>
   where
     T: AsRef<str>,
@@ -129,12 +163,35 @@ impl Database {
 }
 
 pub fn open(mode: Mode) -> Result<Database> {
-  let path = crate::helpers::path::cache()?.join(mode.as_ref());
+  open_path(crate::helpers::path::cache()?.join(mode.as_ref()))
+}
+
+/// 打开临时数据库, 供测试使用
+#[cfg(test)]
+pub fn open_temp() -> Result<Database> {
+  open_path(std::env::temp_dir().join(format!("wukong-{}", crate::helpers::id::gen())))
+}
+
+fn open_path(path: PathBuf) -> Result<Database> {
   let mut opts = Options::default();
   opts.create_if_missing(true);
   let cfs = if path.exists() { DB::list_cf(&opts, &path)? } else { vec![] };
   let db = DB::open_cf(&opts, path, cfs)?;
-  Ok(Database(Arc::new(RwLock::new(db))))
+  Ok(Database(Arc::new(RwLock::new(db)), false))
+}
+
+/// 只读打开, 多个进程可同时读取
+/// ---
+/// 数据库不存在时按读写打开
+pub fn open_read_only(mode: Mode) -> Result<Database> {
+  let path = crate::helpers::path::cache()?.join(mode.as_ref());
+  if !path.exists() {
+    return open(mode);
+  }
+  let opts = Options::default();
+  let cfs = DB::list_cf(&opts, &path)?;
+  let db = DB::open_cf_for_read_only(&opts, path, cfs, false)?;
+  Ok(Database(Arc::new(RwLock::new(db)), true))
 }
 
 #[cfg(test)]
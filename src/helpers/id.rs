@@ -1,6 +1,8 @@
-use uuid::Uuid;
+use rand::Rng;
+use uuid::Builder;
 
-/// 随机32位ID
+/// 随机32位ID, 由运行随机数生成器生成
 pub fn gen() -> String {
-  Uuid::new_v4().to_string().replace("-", "")
+  let bytes = super::random::with_rng(|rng| rng.gen());
+  Builder::from_random_bytes(bytes).into_uuid().simple().to_string()
 }
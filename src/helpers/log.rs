@@ -1,11 +1,20 @@
 use super::constants::Environment;
 use anyhow::Result;
+use once_cell::sync::OnceCell;
 use std::str::FromStr;
 use time::{macros::format_description, UtcOffset};
 // use tracing::subscriber::DefaultGuard;
 use tracing_subscriber::{filter::LevelFilter, fmt::time::OffsetTime, layer::SubscriberExt};
 
+static INIT: OnceCell<()> = OnceCell::new();
+
+/// 初始化日志, 重复调用时忽略
 pub fn init() -> Result<()> {
+  INIT.get_or_try_init(init_subscriber)?;
+  Ok(())
+}
+
+fn init_subscriber() -> Result<()> {
   let level =
     LevelFilter::from_str(Environment::LogLevel.value().unwrap_or("debug".into()).as_str())?;
 
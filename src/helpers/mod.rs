@@ -4,4 +4,5 @@ pub mod date;
 pub mod id;
 pub mod log;
 pub mod path;
+pub mod random;
 pub mod runtime;
@@ -0,0 +1,16 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, SeedableRng};
+
+/// 运行随机数生成器, ID等随机数均由此生成
+static RNG: Lazy<Mutex<StdRng>> = Lazy::new(|| Mutex::new(StdRng::from_entropy()));
+
+/// 设置随机种子, 相同种子生成相同的随机序列
+pub fn seed(seed: u64) {
+  *RNG.lock() = StdRng::seed_from_u64(seed);
+}
+
+/// 使用运行随机数生成器
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+  f(&mut RNG.lock())
+}
@@ -1,14 +1,19 @@
 use anyhow::Result;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::runtime::{Builder, Runtime};
 
+static RAYON: OnceCell<()> = OnceCell::new();
+
+/// 初始化rayon全局线程池, 重复调用时忽略
 pub fn init_rayon() -> Result<()> {
-  let nums = num_cpus::get();
-  rayon::ThreadPoolBuilder::new()
-    .thread_name(|n| format!("WuKong-Computation-{}", n))
-    .num_threads(if nums < 2 { 2 } else { nums })
-    .build_global()?;
+  RAYON.get_or_try_init(|| {
+    let nums = num_cpus::get();
+    rayon::ThreadPoolBuilder::new()
+      .thread_name(|n| format!("WuKong-Computation-{}", n))
+      .num_threads(if nums < 2 { 2 } else { nums })
+      .build_global()
+  })?;
   Ok(())
 }
 
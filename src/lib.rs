@@ -1,9 +1,21 @@
 mod api;
+mod book;
 mod engine;
+mod gateway;
 mod global;
 mod helpers;
+mod market;
+mod matcher;
 mod models;
+mod monte_carlo;
+mod optimize;
+mod report;
+mod risk;
+mod sizing;
+mod symbol;
 mod types;
+mod universe;
+mod walk_forward;
 
 use pyo3::prelude::*;
 
@@ -13,8 +25,24 @@ fn wukong(m: &Bound<'_, PyModule>) -> PyResult<()> {
   m.add_class::<types::Mode>()?;
   m.add_class::<types::Type>()?;
   m.add_class::<types::Side>()?;
+  m.add_class::<types::Market>()?;
+  m.add_class::<types::MarginMode>()?;
+  m.add_class::<types::FeeAsset>()?;
+  m.add_class::<types::Intrabar>()?;
+  m.add_class::<types::Replay>()?;
+  m.add_class::<types::Resample>()?;
   m.add_class::<types::TimeFrame>()?;
   m.add_class::<types::OrderStatus>()?;
+  m.add_class::<models::Candle>()?;
+  m.add_class::<models::Tick>()?;
+  m.add_class::<models::Trade>()?;
+  m.add_class::<book::Book>()?;
+  m.add_class::<models::RiskLimits>()?;
+  m.add_class::<models::Latency>()?;
+  m.add_class::<report::Report>()?;
+  m.add_class::<monte_carlo::MonteCarlo>()?;
+  m.add_class::<models::UniverseRule>()?;
+  m.add_class::<models::Config>()?;
   m.add_function(wrap_pyfunction!(api::debug, m)?)?;
   m.add_function(wrap_pyfunction!(api::info, m)?)?;
   m.add_function(wrap_pyfunction!(api::warn, m)?)?;
@@ -28,26 +56,65 @@ fn wukong(m: &Bound<'_, PyModule>) -> PyResult<()> {
   m.add_function(wrap_pyfunction!(api::account_available_cash, m)?)?;
   m.add_function(wrap_pyfunction!(api::account_margin, m)?)?;
   m.add_function(wrap_pyfunction!(api::account_pnl, m)?)?;
+  m.add_function(wrap_pyfunction!(api::account_equity, m)?)?;
+  m.add_function(wrap_pyfunction!(api::account_balance, m)?)?;
+  m.add_function(wrap_pyfunction!(api::account_available_balance, m)?)?;
+  m.add_function(wrap_pyfunction!(api::account_balances, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_long_size, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_long_available_size, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_long_price, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_long_margin, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_long_pnl, m)?)?;
+  m.add_function(wrap_pyfunction!(api::position_long_liquidation_price, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_short_size, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_short_available_size, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_short_price, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_short_margin, m)?)?;
   m.add_function(wrap_pyfunction!(api::position_short_pnl, m)?)?;
+  m.add_function(wrap_pyfunction!(api::position_short_liquidation_price, m)?)?;
+  m.add_function(wrap_pyfunction!(api::pair_market, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_leverage, m)?)?;
+  m.add_function(wrap_pyfunction!(api::pair_margin_mode, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_margin, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_mark_price, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_order, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_open_orders, m)?)?;
   m.add_function(wrap_pyfunction!(api::pair_order_ids, m)?)?;
+  m.add_function(wrap_pyfunction!(api::place_order, m)?)?;
+  m.add_function(wrap_pyfunction!(api::amend_order, m)?)?;
+  m.add_function(wrap_pyfunction!(api::cancel_order, m)?)?;
+  m.add_function(wrap_pyfunction!(api::set_leverage, m)?)?;
+  m.add_function(wrap_pyfunction!(api::set_margin_mode, m)?)?;
+  m.add_function(wrap_pyfunction!(api::add_margin, m)?)?;
+  m.add_function(wrap_pyfunction!(api::reduce_margin, m)?)?;
+  m.add_function(wrap_pyfunction!(api::halt, m)?)?;
+  m.add_function(wrap_pyfunction!(api::resume, m)?)?;
+  m.add_function(wrap_pyfunction!(api::is_halted, m)?)?;
+  m.add_function(wrap_pyfunction!(api::symbol_info, m)?)?;
+  m.add_function(wrap_pyfunction!(api::round_price, m)?)?;
+  m.add_function(wrap_pyfunction!(api::round_size, m)?)?;
+  m.add_function(wrap_pyfunction!(api::size_by_cash, m)?)?;
+  m.add_function(wrap_pyfunction!(api::size_by_risk, m)?)?;
+  m.add_function(wrap_pyfunction!(api::size_by_volatility, m)?)?;
+  m.add_function(wrap_pyfunction!(api::size_by_kelly, m)?)?;
+  m.add_function(wrap_pyfunction!(api::subscribe, m)?)?;
+  m.add_function(wrap_pyfunction!(api::unsubscribe, m)?)?;
+  m.add_function(wrap_pyfunction!(api::strategy_name, m)?)?;
   m.add_function(wrap_pyfunction!(api::is_running, m)?)?;
   m.add_function(wrap_pyfunction!(api::trade_time, m)?)?;
   m.add_function(wrap_pyfunction!(api::benchmark, m)?)?;
   m.add_function(wrap_pyfunction!(api::symbols, m)?)?;
   m.add_function(wrap_pyfunction!(api::run, m)?)?;
+  m.add_function(wrap_pyfunction!(api::run_portfolio, m)?)?;
+  m.add_function(wrap_pyfunction!(api::book, m)?)?;
+  m.add_function(wrap_pyfunction!(api::report, m)?)?;
+  m.add_function(wrap_pyfunction!(api::trades, m)?)?;
+  m.add_function(wrap_pyfunction!(api::equity_curve, m)?)?;
+  m.add_function(wrap_pyfunction!(api::import_agg_trades, m)?)?;
+  m.add_function(wrap_pyfunction!(api::import_depth, m)?)?;
+  m.add_function(wrap_pyfunction!(api::optimize, m)?)?;
+  m.add_function(wrap_pyfunction!(api::walk_forward, m)?)?;
+  m.add_function(wrap_pyfunction!(api::monte_carlo, m)?)?;
+  m.add_function(wrap_pyfunction!(api::run_worker, m)?)?;
   Ok(())
 }
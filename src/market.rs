@@ -0,0 +1,266 @@
+use crate::{
+  helpers::database::Database,
+  models::{AggTrade, Candle, DepthUpdate},
+  types::TimeFrame,
+};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::{io::Read, path::Path};
+
+/// K线表
+pub fn candle_table(symbol: &str, timeframe: TimeFrame) -> String {
+  format!("candle_{}_{}", symbol, timeframe)
+}
+
+/// 获取K线
+pub fn candle(
+  db: &Database,
+  symbol: &str,
+  timeframe: TimeFrame,
+  time: i64,
+) -> Result<Option<Candle>> {
+  db.get(candle_table(symbol, timeframe), time)
+}
+
+/// 获取时间范围内的K线 `[begin, end)`
+pub fn candles(
+  db: &Database,
+  symbol: &str,
+  timeframe: TimeFrame,
+  begin: i64,
+  end: i64,
+) -> Result<Vec<Candle>> {
+  let items = db.get_range::<_, _, i64, Candle>(candle_table(symbol, timeframe), begin, end - 1)?;
+  Ok(items.into_iter().map(|(_, v)| v).collect())
+}
+
+/// 保存K线
+pub fn save_candles(
+  db: &Database,
+  symbol: &str,
+  timeframe: TimeFrame,
+  candles: &[Candle],
+) -> Result<()> {
+  db.batch_set(candle_table(symbol, timeframe), candles.iter().map(|v| (v.time, v.clone())))
+}
+
+/// 时间范围内的成交额 `[begin, end)`
+pub fn quote_volume(db: &Database, symbol: &str, begin: i64, end: i64) -> Result<f64> {
+  Ok(candles(db, symbol, TimeFrame::Minute, begin, end)?.iter().map(|v| v.amount).sum())
+}
+
+/// 归集成交表
+pub fn agg_trade_table(symbol: &str) -> String {
+  format!("agg_trade_{}", symbol)
+}
+
+/// 获取时间范围内的归集成交 `[begin, end)`, 按时间及ID排序
+pub fn agg_trades(db: &Database, symbol: &str, begin: i64, end: i64) -> Result<Vec<AggTrade>> {
+  let items = db.get_range::<_, _, (i64, i64), AggTrade>(
+    agg_trade_table(symbol),
+    (begin, i64::MIN),
+    (end - 1, i64::MAX),
+  )?;
+  Ok(items.into_iter().map(|(_, v)| v).collect())
+}
+
+/// 保存归集成交
+pub fn save_agg_trades(db: &Database, symbol: &str, trades: &[AggTrade]) -> Result<()> {
+  db.batch_set(agg_trade_table(symbol), trades.iter().map(|v| ((v.time, v.id), v.clone())))
+}
+
+/// 导入币安 `aggTrades` 历史数据文件, 返回导入数量
+/// ---
+/// - 支持 `.zip` 压缩包及解压后的 `.csv` 文件
+/// - 列 : agg_trade_id, price, quantity, first_trade_id, last_trade_id, transact_time, is_buyer_maker
+/// - 跳过表头
+pub fn import_agg_trades(db: &Database, symbol: &str, path: impl AsRef<Path>) -> Result<usize> {
+  let mut count = 0;
+  for content in read_file(path)? {
+    let mut trades = vec![];
+    for line in content.lines().filter(|v| !v.trim().is_empty()) {
+      if let Some(trade) = parse_agg_trade(line)? {
+        trades.push(trade);
+      }
+    }
+    save_agg_trades(db, symbol, &trades)?;
+    count += trades.len();
+  }
+  Ok(count)
+}
+
+/// 盘口增量更新表
+pub fn depth_table(symbol: &str) -> String {
+  format!("depth_{}", symbol)
+}
+
+/// 盘口快照表
+pub fn depth_snapshot_table(symbol: &str) -> String {
+  format!("depth_snapshot_{}", symbol)
+}
+
+/// 获取时间范围内的盘口快照及增量更新 `[begin, end)`, 按时间排序, 同一时间快照在前
+pub fn depth_updates(
+  db: &Database,
+  symbol: &str,
+  begin: i64,
+  end: i64,
+) -> Result<Vec<DepthUpdate>> {
+  let mut items = vec![];
+  for table in [depth_snapshot_table(symbol), depth_table(symbol)] {
+    let range =
+      db.get_range::<_, _, (i64, i64), DepthUpdate>(table, (begin, i64::MIN), (end - 1, i64::MAX))?;
+    items.extend(range.into_iter().map(|(_, v)| v));
+  }
+  items.sort_by_key(|v| (v.time, !v.snapshot, v.update_id));
+  Ok(items)
+}
+
+/// 时间之前最近的盘口快照
+pub fn depth_snapshot(db: &Database, symbol: &str, time: i64) -> Result<Option<DepthUpdate>> {
+  let begin = time - 24 * 60 * 60 * 1000;
+  let items = db.get_range::<_, _, (i64, i64), DepthUpdate>(
+    depth_snapshot_table(symbol),
+    (begin, i64::MIN),
+    (time - 1, i64::MAX),
+  )?;
+  Ok(items.into_iter().next_back().map(|(_, v)| v))
+}
+
+/// 保存盘口快照及增量更新
+pub fn save_depth_updates(db: &Database, symbol: &str, updates: &[DepthUpdate]) -> Result<()> {
+  for (table, snapshot) in [(depth_snapshot_table(symbol), true), (depth_table(symbol), false)] {
+    db.batch_set(
+      table,
+      updates.iter().filter(|v| v.snapshot == snapshot).map(|v| ((v.time, v.update_id), v.clone())),
+    )?;
+  }
+  Ok(())
+}
+
+/// 导入录制的盘口数据文件, 返回导入数量
+/// ---
+/// - 支持 `.zip` 压缩包及解压后的文件
+/// - 每行一条币安原始消息, 格式见 `parse_depth`
+pub fn import_depth(db: &Database, symbol: &str, path: impl AsRef<Path>) -> Result<usize> {
+  let mut count = 0;
+  for content in read_file(path)? {
+    let mut updates = vec![];
+    for line in content.lines().filter(|v| !v.trim().is_empty()) {
+      updates.push(parse_depth(&serde_json::from_str(line)?)?);
+    }
+    save_depth_updates(db, symbol, &updates)?;
+    count += updates.len();
+  }
+  Ok(count)
+}
+
+/// 解析币安盘口消息
+/// ---
+/// - 全量快照 : 接口 `/fapi/v1/depth` 返回, 含 `lastUpdateId`
+/// - 增量更新 : 推送 `depthUpdate`, 含 `u`, 支持组合推送的 `data` 包装
+/// - 时间取撮合时间 `T`, 没有时取事件时间 `E`
+pub fn parse_depth(value: &Value) -> Result<DepthUpdate> {
+  let value = value.get("data").unwrap_or(value);
+  let time =
+    value["T"].as_i64().or(value["E"].as_i64()).ok_or(anyhow!("盘口缺少时间 : {value}"))?;
+  let levels = |v: &Value| {
+    v.as_array()
+      .into_iter()
+      .flatten()
+      .map(|level| {
+        let f = |i: usize| level[i].as_str().and_then(|v| v.parse::<f64>().ok());
+        f(0).zip(f(1)).ok_or(anyhow!("盘口档位格式错误 : {level}"))
+      })
+      .collect::<Result<Vec<_>>>()
+  };
+  if let Some(update_id) = value["lastUpdateId"].as_i64() {
+    Ok(DepthUpdate {
+      time,
+      update_id,
+      snapshot: true,
+      bids: levels(&value["bids"])?,
+      asks: levels(&value["asks"])?,
+    })
+  } else {
+    Ok(DepthUpdate {
+      time,
+      update_id: value["u"].as_i64().ok_or(anyhow!("盘口缺少更新ID : {value}"))?,
+      snapshot: false,
+      bids: levels(&value["b"])?,
+      asks: levels(&value["a"])?,
+    })
+  }
+}
+
+/// 读取数据文件, `.zip` 压缩包返回其中每个文件的内容
+fn read_file(path: impl AsRef<Path>) -> Result<Vec<String>> {
+  let path = path.as_ref();
+  if !path.extension().is_some_and(|v| v.eq_ignore_ascii_case("zip")) {
+    return Ok(vec![std::fs::read_to_string(path)?]);
+  }
+  let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+  let mut contents = vec![];
+  for i in 0..archive.len() {
+    let mut content = String::new();
+    archive.by_index(i)?.read_to_string(&mut content)?;
+    contents.push(content);
+  }
+  Ok(contents)
+}
+
+/// 解析 `aggTrades` 文件的一行, 表头返回 `None`
+/// ---
+/// 成交时间为微秒时转换为毫秒
+fn parse_agg_trade(line: &str) -> Result<Option<AggTrade>> {
+  let fields = line.split(',').map(|v| v.trim()).collect::<Vec<_>>();
+  if fields.first().is_some_and(|v| v.parse::<i64>().is_err()) {
+    return Ok(None);
+  }
+  let field = |i: usize| fields.get(i).copied().ok_or(anyhow!("归集成交格式错误 : {line}"));
+  let time = field(5)?.parse::<i64>()?;
+  Ok(Some(AggTrade {
+    id: field(0)?.parse()?,
+    price: field(1)?.parse()?,
+    size: field(2)?.parse()?,
+    time: if time > 10_i64.pow(14) { time / 1000 } else { time },
+    buyer_maker: field(6)?.eq_ignore_ascii_case("true"),
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use anyhow::Result;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let header =
+      "agg_trade_id,price,quantity,first_trade_id,last_trade_id,transact_time,is_buyer_maker";
+    assert!(super::parse_agg_trade(header)?.is_none());
+    let trade =
+      super::parse_agg_trade("26129,0.01633102,4.70443515,27781,27781,1498793709153,true")?
+        .expect("解析失败");
+    assert_eq!(trade.id, 26129);
+    assert_eq!(trade.time, 1498793709153);
+    assert!(trade.buyer_maker);
+    let trade = super::parse_agg_trade("1,100.5,2,1,1,1735689600000123,False")?.expect("解析失败");
+    assert_eq!(trade.time, 1735689600000);
+    assert!(!trade.buyer_maker);
+
+    let snapshot = serde_json::json!({
+      "lastUpdateId": 100, "E": 1700000000000_i64, "T": 1700000000001_i64,
+      "bids": [["99.5", "2"]], "asks": [["100.5", "3"]]
+    });
+    let snapshot = super::parse_depth(&snapshot)?;
+    assert!(snapshot.snapshot);
+    assert_eq!((snapshot.time, snapshot.update_id), (1700000000001, 100));
+    let update = serde_json::json!({"stream": "btcusdt@depth", "data": {
+      "e": "depthUpdate", "E": 1700000000100_i64, "U": 101, "u": 102, "pu": 100,
+      "b": [["99.5", "0"]], "a": []
+    }});
+    let update = super::parse_depth(&update)?;
+    assert!(!update.snapshot);
+    assert_eq!((update.time, update.update_id, update.bids[0]), (1700000000100, 102, (99.5, 0.0)));
+    Ok(())
+  }
+}
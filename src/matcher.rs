@@ -0,0 +1,759 @@
+use crate::{
+  models::{AggTrade, Candle, Context, Event, Order, Pair, Position, Trade},
+  types::{FeeAsset, Intrabar, OrderStatus, Replay, Side, Type},
+};
+use anyhow::{anyhow, ensure, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
+
+/// 已提示回退路径的(交易对, 日期), 每个交易对每天只提示一次
+static FALLBACKS: Lazy<Mutex<BTreeSet<(String, i64)>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+/// 下单
+/// ---
+/// - 开仓订单冻结保证金, 减仓订单冻结可用持仓
+/// - 现货买单冻结资金, 卖单冻结可用资产, 不支持做空
+/// - 现货买单手续费从计价资产扣除时, 按吃单费率额外冻结手续费
+/// - 资金或持仓不足时拒绝
+/// - 可立即成交时, 按标记价格吃单成交
+pub fn place_order(ctx: &mut Context, mut order: Order) -> Result<Order> {
+  let available_cash = ctx.pairs.get(&order.symbol).map(|v| ctx.available(v)).unwrap_or_default();
+  let spot_fee = ctx.config.spot_fee;
+  let queue = ctx.books.get(&order.symbol).map(|v| v.size_at(order.is_buy(), order.price));
+  let pair = ctx.pairs.get_mut(&order.symbol).ok_or(anyhow!("交易对不存在 : {}", order.symbol))?;
+
+  let reason = if pair.is_spot() && order.side == Side::Short {
+    Some("现货不支持做空".to_owned())
+  } else if pair.is_spot() && order.reduce {
+    let available =
+      ctx.account.available_balances.get(&pair.info.base_asset).cloned().unwrap_or_default();
+    (order.size > available).then(|| format!("可用资产不足 : {} > {available}", order.size))
+  } else if order.reduce {
+    let position = match order.side {
+      Side::Long => &mut pair.long,
+      Side::Short => &mut pair.short,
+    };
+    if order.size > position.available_size {
+      Some(format!("可用持仓不足 : {} > {}", order.size, position.available_size))
+    } else {
+      position.available_size -= order.size;
+      None
+    }
+  } else {
+    order.margin =
+      pair.info.notional(order.price, order.size) / order.leverage * fee_buffer(pair, spot_fee);
+    if order.margin > available_cash {
+      Some(format!("可用资金不足 : {} > {available_cash}", order.margin))
+    } else {
+      None
+    }
+  };
+
+  if let Some(reason) = reason {
+    tracing::warn!("订单被拒绝 : {} {} {}", order.symbol, order.id, reason);
+    order.margin = Decimal::ZERO;
+    order.status = OrderStatus::Rejected;
+    order.reason = Some(reason);
+    pair.orders.insert(order.id.clone(), order.clone());
+    ctx.events.push(Event::Order(order.clone()));
+    return Ok(order);
+  }
+
+  let (symbol, id) = (order.symbol.clone(), order.id.clone());
+  order.status = OrderStatus::Pending;
+  order.queue = queue;
+  pair.orders.insert(id.clone(), order.clone());
+  ctx.events.push(Event::Order(order));
+  ctx.refresh_account();
+  match_order(ctx, &symbol, &id)?;
+
+  ctx
+    .pairs
+    .get(&symbol)
+    .and_then(|v| v.orders.get(&id))
+    .cloned()
+    .ok_or(anyhow!("订单不存在 : {id}"))
+}
+
+/// 修改订单
+/// ---
+/// - 保留订单ID及已成交部分, 仅修改剩余部分
+/// - 开仓订单按新价格及剩余数量重新冻结保证金
+/// - 减仓订单按数量变化冻结或释放可用持仓
+/// - 修改价格后重新排队
+/// - 修改后价格可立即成交时, 按标记价格吃单成交
+pub fn amend_order(
+  ctx: &mut Context,
+  symbol: &str,
+  id: &str,
+  price: Option<Decimal>,
+  size: Option<Decimal>,
+) -> Result<Order> {
+  let trade_time = ctx.trade_time;
+  let available_cash = ctx.pairs.get(symbol).map(|v| ctx.available(v)).unwrap_or_default();
+  let spot_fee = ctx.config.spot_fee;
+  let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let buffer = fee_buffer(pair, spot_fee);
+  let info = pair.info.clone();
+  let spot = pair.is_spot().then(|| pair.info.base_asset.clone());
+  let order = pair.orders.get_mut(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+  ensure!(order.is_accepted(), "订单尚未挂单或已结束 : {id}");
+
+  let mut amended = order.clone();
+  amended.amend(trade_time, price, size)?;
+  if amended.price != order.price {
+    amended.queue = ctx.books.get(symbol).map(|v| v.size_at(amended.is_buy(), amended.price));
+  }
+
+  if let Some(base) = spot.filter(|_| amended.reduce) {
+    let delta = amended.remain_size() - order.remain_size();
+    let available = ctx.account.available_balances.get(&base).cloned().unwrap_or_default();
+    ensure!(delta <= available, "可用资产不足 : {delta} > {available}");
+  } else if amended.reduce {
+    let position = match amended.side {
+      Side::Long => &mut pair.long,
+      Side::Short => &mut pair.short,
+    };
+    let delta = amended.remain_size() - order.remain_size();
+    ensure!(
+      delta <= position.available_size,
+      "可用持仓不足 : {delta} > {}",
+      position.available_size
+    );
+    position.available_size -= delta;
+  } else {
+    amended.margin =
+      info.notional(amended.price, amended.remain_size()) / amended.leverage * buffer;
+    let delta = amended.margin - order.margin;
+    ensure!(delta <= available_cash, "可用资金不足 : {delta} > {available_cash}");
+  }
+
+  *order = amended.clone();
+  ctx.events.push(Event::Order(amended));
+  ctx.refresh_account();
+  match_order(ctx, symbol, id)?;
+
+  ctx.pairs.get(symbol).and_then(|v| v.orders.get(id)).cloned().ok_or(anyhow!("订单不存在 : {id}"))
+}
+
+/// 撤单
+/// ---
+/// 释放未成交部分冻结的保证金或可用持仓
+pub fn cancel_order(ctx: &mut Context, symbol: &str, id: &str) -> Result<Order> {
+  let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let spot = pair.is_spot();
+  let order = pair.orders.get_mut(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+  ensure!(order.is_open(), "订单已结束 : {id}");
+  if order.reduce && order.is_accepted() && !spot {
+    let position = match order.side {
+      Side::Long => &mut pair.long,
+      Side::Short => &mut pair.short,
+    };
+    position.available_size = (position.available_size + order.remain_size()).min(position.size);
+  }
+  order.margin = Decimal::ZERO;
+  order.status = OrderStatus::Canceled;
+  let order = order.clone();
+  ctx.events.push(Event::Order(order.clone()));
+  ctx.refresh_account();
+  Ok(order)
+}
+
+/// 按K线撮合挂单
+/// ---
+/// - 限价买单最低价触及时成交, 限价卖单最高价触及时成交
+/// - 开盘价优于挂单价时按开盘价成交
+/// - 按 `Config::intrabar` 假设的K线内价格路径确定成交顺序, 使用的假设记录在成交上
+/// - 撮合完成后标记价格更新为收盘价
+pub fn match_candle(ctx: &mut Context, symbol: &str, candle: &Candle) -> Result<()> {
+  let has_orders =
+    ctx.pairs.get(symbol).is_some_and(|v| v.orders.values().any(|o| o.is_accepted()));
+  if has_orders {
+    let path = intrabar_path(ctx, symbol, candle)?;
+    match_path(ctx, symbol, &path, Decimal::ZERO, Decimal::ONE)?;
+  }
+  close_candle(ctx, symbol, candle)
+}
+
+/// 按K线内价格路径的一段撮合挂单
+/// ---
+/// - `from` 及 `to` 为K线内时间比例, 范围 `[0, 1]`, 路径各线段按时间均分
+/// - 仅撮合在该段内首次可成交的挂单, 用于在途订单请求到达前后分段撮合
+pub fn match_path(
+  ctx: &mut Context,
+  symbol: &str,
+  path: &(Intrabar, Vec<Decimal>),
+  from: Decimal,
+  to: Decimal,
+) -> Result<()> {
+  let (intrabar, path) = path;
+  let pair = ctx.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let mut orders = pair.orders.values().filter(|o| o.is_accepted()).collect::<Vec<_>>();
+  if orders.is_empty() || path.is_empty() {
+    return Ok(());
+  }
+  orders.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.id.cmp(&b.id)));
+
+  let segments = Decimal::from(path.len() - 1);
+  let (begin, end) = (from * segments, to * segments);
+  let mut part = vec![path_price(path, from)];
+  part.extend(
+    path
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| {
+        let i = Decimal::from(*i);
+        i > begin && i < end
+      })
+      .map(|(_, v)| *v),
+  );
+  if end > begin {
+    part.push(path_price(path, to));
+  }
+  let mut fills = orders
+    .into_iter()
+    .filter_map(|o| touch(&part, o).map(|(at, price, maker)| (at, o.id.clone(), price, maker)))
+    .collect::<Vec<_>>();
+  fills.sort_by_key(|v| v.0);
+
+  for (_, id, price, maker) in fills {
+    let size = ctx.pairs.get(symbol).and_then(|v| v.orders.get(&id)).map(|v| v.remain_size());
+    fill(ctx, symbol, &id, price, size.unwrap_or_default(), maker, Some(*intrabar))?;
+  }
+  Ok(())
+}
+
+/// K线内价格路径在时间比例 `at` 处的价格, 线段内按线性插值
+pub fn path_price(path: &[Decimal], at: Decimal) -> Decimal {
+  let Some(last) = path.len().checked_sub(1) else {
+    return Decimal::ZERO;
+  };
+  let position = at.max(Decimal::ZERO).min(Decimal::ONE) * Decimal::from(last);
+  let i = position.floor();
+  let ratio = position - i;
+  let i = usize::try_from(i).unwrap_or_default().min(last);
+  if ratio.is_zero() || i == last {
+    return path[i];
+  }
+  path[i] + (path[i + 1] - path[i]) * ratio
+}
+
+/// K线撮合结束, 标记价格更新为收盘价并检查强平
+pub fn close_candle(ctx: &mut Context, symbol: &str, candle: &Candle) -> Result<()> {
+  if let Some(pair) = ctx.pairs.get_mut(symbol) {
+    pair.mark_price = Decimal::try_from(candle.close)?;
+  }
+  ctx.refresh_account();
+  liquidate(ctx, symbol, Decimal::try_from(candle.low)?, Decimal::try_from(candle.high)?)
+}
+
+/// K线内价格路径, 返回实际使用的假设
+pub fn intrabar_path(
+  ctx: &Context,
+  symbol: &str,
+  candle: &Candle,
+) -> Result<(Intrabar, Vec<Decimal>)> {
+  let open = Decimal::try_from(candle.open)?;
+  let high = Decimal::try_from(candle.high)?;
+  let low = Decimal::try_from(candle.low)?;
+  let close = Decimal::try_from(candle.close)?;
+  let ohlc = vec![open, high, low, close];
+  let olhc = vec![open, low, high, close];
+  let nearest = if high - open <= open - low { ohlc.clone() } else { olhc.clone() };
+
+  let path = match ctx.config.intrabar {
+    Intrabar::OpenHighLowClose => (Intrabar::OpenHighLowClose, ohlc),
+    Intrabar::OpenLowHighClose => (Intrabar::OpenLowHighClose, olhc),
+    Intrabar::NearestFirst => (Intrabar::NearestFirst, nearest),
+    Intrabar::Pessimistic => {
+      let pair = ctx.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+      let net = pair.long.size - pair.short.size;
+      let path = if net.is_zero() {
+        nearest
+      } else if net.is_sign_positive() {
+        olhc
+      } else {
+        ohlc
+      };
+      (Intrabar::Pessimistic, path)
+    },
+    Intrabar::DrillDown => {
+      let trades = crate::market::agg_trades(&ctx.db, symbol, candle.time, candle.time + 60_000)?;
+      if trades.is_empty() {
+        if FALLBACKS.lock().insert((symbol.to_owned(), candle.time / 86_400_000)) {
+          tracing::warn!("{symbol} 缺少归集成交数据, K线内路径回退为 NearestFirst");
+        }
+        (Intrabar::NearestFirst, nearest)
+      } else {
+        let mut path = vec![open];
+        for trade in trades.iter() {
+          let price = Decimal::try_from(trade.price)?;
+          if path.last() != Some(&price) {
+            path.push(price);
+          }
+        }
+        if path.last() != Some(&close) {
+          path.push(close);
+        }
+        (Intrabar::DrillDown, path)
+      }
+    },
+  };
+  Ok(path)
+}
+
+/// 订单在价格路径上首次可成交的位置、成交价格及是否挂单成交
+/// ---
+/// 位置为路径线段序号加线段内比例, 开盘即可成交时为0
+fn touch(path: &[Decimal], order: &Order) -> Option<(Decimal, Decimal, bool)> {
+  let open = *path.first()?;
+  if order.r#type == Type::Market {
+    return Some((Decimal::ZERO, open, false));
+  }
+  let reached = |p: Decimal| if order.is_buy() { p <= order.price } else { p >= order.price };
+  if reached(open) {
+    return Some((Decimal::ZERO, open, true));
+  }
+  path.windows(2).enumerate().find(|(_, v)| reached(v[1])).map(|(i, v)| {
+    let ratio = (v[0] - order.price) / (v[0] - v[1]);
+    (Decimal::from(i) + ratio, order.price, true)
+  })
+}
+
+/// 按归集成交撮合挂单
+/// ---
+/// - 市价单按成交价吃单成交
+/// - 限价单按挂单价成交, 成交价穿过挂单价时全部成交, 不受该笔成交数量限制
+/// - 成交价等于挂单价且对手方主动成交时 :
+///   - 按盘口回放时, 成交数量先消耗排队位置, 剩余部分成交
+///   - 否则按时间顺序成交, 累计成交数量不超过该笔成交数量
+/// - 撮合完成后标记价格更新为成交价
+pub fn match_agg_trade(ctx: &mut Context, symbol: &str, trade: &AggTrade) -> Result<()> {
+  let price = Decimal::try_from(trade.price)?;
+  let mut volume = Decimal::try_from(trade.size)?;
+  let queued = ctx.config.replay == Replay::Depth;
+
+  let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let mut orders = pair.orders.values_mut().filter(|o| o.is_accepted()).collect::<Vec<_>>();
+  orders.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.id.cmp(&b.id)));
+  let mut fills = vec![];
+  for o in orders {
+    if o.r#type == Type::Market {
+      fills.push((o.id.clone(), price, o.remain_size(), false));
+      continue;
+    }
+    let (through, touched) = if o.is_buy() {
+      (price < o.price, price == o.price && trade.buyer_maker)
+    } else {
+      (price > o.price, price == o.price && !trade.buyer_maker)
+    };
+    if through {
+      fills.push((o.id.clone(), o.price, o.remain_size(), true));
+    } else if queued && touched {
+      let ahead = o.queue.unwrap_or_default();
+      o.queue = Some((ahead - volume).max(Decimal::ZERO));
+      let size = o.remain_size().min((volume - ahead).max(Decimal::ZERO));
+      if !size.is_zero() {
+        volume -= size;
+        fills.push((o.id.clone(), o.price, size, true));
+      }
+    } else if touched && !volume.is_zero() {
+      let size = o.remain_size().min(volume);
+      volume -= size;
+      fills.push((o.id.clone(), o.price, size, true));
+    }
+  }
+
+  for (id, price, size, maker) in fills {
+    fill(ctx, symbol, &id, price, size, maker, None)?;
+  }
+
+  if let Some(pair) = ctx.pairs.get_mut(symbol) {
+    pair.mark_price = price;
+  }
+  ctx.refresh_account();
+  liquidate(ctx, symbol, price, price)
+}
+
+/// 强制平仓
+/// ---
+/// - 最低价或最高价触及强平价格时, 按强平价格平仓并撤销该方向的减仓订单
+/// - 逐仓亏损以仓位保证金为限, 全仓按强平价格结算
+/// - 强平成交的订单ID为空, 不收手续费
+pub fn liquidate(ctx: &mut Context, symbol: &str, low: Decimal, high: Decimal) -> Result<()> {
+  let trade_time = ctx.trade_time;
+  let settle_price = ctx.pairs.get(symbol).map(|v| ctx.asset_price(&v.info.settle_asset));
+  let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  if pair.is_spot() {
+    return Ok(());
+  }
+  let isolated = pair.is_isolated();
+  let (mut liquidated, mut settled) = (false, Decimal::ZERO);
+  for side in [Side::Long, Side::Short] {
+    let (position, price) = match side {
+      Side::Long => (&mut pair.long, low),
+      Side::Short => (&mut pair.short, high),
+    };
+    if !position.is_liquidated(price) {
+      continue;
+    }
+    let (size, price) = (position.size, position.liquidation_price);
+    let pnl =
+      if isolated { -position.margin } else { pair.info.pnl(side, position.price, price, size) };
+    tracing::warn!("强制平仓 : {symbol} {side} {size} @ {price} 盈亏 {pnl}");
+    *position = Position::new(symbol, side, position.leverage);
+    liquidated = true;
+    settled += pnl;
+
+    for order in pair.orders.values_mut().filter(|o| o.is_open() && o.reduce && o.side == side) {
+      order.status = OrderStatus::Canceled;
+      ctx.events.push(Event::Order(order.clone()));
+    }
+    let trade = Trade {
+      symbol: symbol.to_owned(),
+      order_id: String::new(),
+      time: trade_time,
+      side,
+      reduce: true,
+      price,
+      size,
+      fee: Decimal::ZERO,
+      fee_asset: pair.info.settle_asset.clone(),
+      pnl,
+      settle_price: settle_price.unwrap_or_default(),
+      maker: false,
+      intrabar: None,
+    };
+    ctx.trades.push(trade.clone());
+    ctx.events.push(Event::Trade(trade));
+  }
+  if liquidated {
+    *ctx.account.balances.entry(pair.info.settle_asset.clone()).or_default() += settled;
+    ctx.refresh_account();
+  }
+  Ok(())
+}
+
+/// 盘口变化后更新限价单排队位置, 同价位挂单减少时排队位置前移
+pub fn update_queues(ctx: &mut Context, symbol: &str) {
+  let (Some(book), Some(pair)) = (ctx.books.get(symbol), ctx.pairs.get_mut(symbol)) else {
+    return;
+  };
+  for order in pair.orders.values_mut().filter(|o| o.is_accepted() && o.r#type == Type::Limit) {
+    let size = book.size_at(order.is_buy(), order.price);
+    order.queue = Some(order.queue.map_or(size, |v| v.min(size)));
+  }
+}
+
+/// 按标记价格撮合订单, 可成交时以吃单成交
+pub fn match_order(ctx: &mut Context, symbol: &str, id: &str) -> Result<()> {
+  let pair = ctx.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let order = pair.orders.get(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+  let mark_price = pair.mark_price;
+  if !order.is_accepted() || mark_price.is_zero() {
+    return Ok(());
+  }
+  let marketable = match order.r#type {
+    Type::Market => true,
+    Type::Limit if order.is_buy() => order.price >= mark_price,
+    Type::Limit => order.price <= mark_price,
+  };
+  if marketable {
+    let size = order.remain_size();
+    fill(ctx, symbol, id, mark_price, size, false, None)?;
+  }
+  Ok(())
+}
+
+/// 开仓冻结系数, 现货买单手续费从计价资产扣除时额外冻结吃单手续费
+fn fee_buffer(pair: &Pair, spot_fee: FeeAsset) -> Decimal {
+  if pair.is_spot() && spot_fee == FeeAsset::Quote {
+    Decimal::ONE + pair.taker_fee
+  } else {
+    Decimal::ONE
+  }
+}
+
+/// 按手续费率成交
+/// ---
+/// 现货买单成交额加手续费超过可用计价资产及订单冻结资金时, 拒绝剩余部分
+pub fn fill(
+  ctx: &mut Context,
+  symbol: &str,
+  id: &str,
+  price: Decimal,
+  size: Decimal,
+  maker: bool,
+  intrabar: Option<Intrabar>,
+) -> Result<()> {
+  let pair = ctx.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let order = pair.orders.get(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+  let fee_rate = if maker { pair.maker_fee } else { pair.taker_fee };
+  let fee = pair.info.notional(price, size.min(order.remain_size())) * fee_rate;
+  let time = ctx.trade_time;
+  if pair.is_spot() && !order.reduce {
+    let cost = match ctx.config.spot_fee {
+      FeeAsset::Received => price * size.min(order.remain_size()),
+      FeeAsset::Quote => price * size.min(order.remain_size()) + fee,
+    };
+    let budget = ctx.available(pair).max(Decimal::ZERO) + order.margin;
+    if cost > budget {
+      let reason = format!("可用资金不足 : {cost} > {budget}");
+      tracing::warn!("订单被拒绝 : {symbol} {id} {reason}");
+      let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+      let order = pair.orders.get_mut(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+      order.margin = Decimal::ZERO;
+      order.status = OrderStatus::Rejected;
+      order.reason = Some(reason);
+      ctx.events.push(Event::Order(order.clone()));
+      ctx.refresh_account();
+      return Ok(());
+    }
+  }
+  settle(ctx, symbol, id, price, size, fee, maker, time, intrabar)
+}
+
+/// 成交
+/// ---
+/// - 更新订单成交信息、仓位及账户资金, 产生成交及订单事件
+/// - 现货按成交额交换计价资产及基础资产, 持仓仅记录成本, 手续费按 `Config::spot_fee` 扣除
+/// - 合约手续费及平仓盈亏计入结算资产余额
+/// - `fee` 以结算资产计, 实盘取交易所实际扣除的手续费
+#[allow(clippy::too_many_arguments)]
+pub fn settle(
+  ctx: &mut Context,
+  symbol: &str,
+  id: &str,
+  price: Decimal,
+  size: Decimal,
+  fee: Decimal,
+  maker: bool,
+  time: DateTime<Utc>,
+  intrabar: Option<Intrabar>,
+) -> Result<()> {
+  let settle_price = ctx.pairs.get(symbol).map(|v| ctx.asset_price(&v.info.settle_asset));
+  let pair = ctx.pairs.get_mut(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  let order = pair.orders.get_mut(id).ok_or(anyhow!("订单不存在 : {id}"))?;
+  let size = size.min(order.remain_size());
+  if size.is_zero() {
+    return Ok(());
+  }
+
+  // 订单
+  let released = order.margin * size / order.remain_size();
+  order.margin -= released;
+  order.deal_price = (order.deal_price * order.deal_size + price * size) / (order.deal_size + size);
+  order.deal_size += size;
+  order.deal_fee += fee;
+  order.status =
+    if order.remain_size().is_zero() { OrderStatus::Completed } else { OrderStatus::Partial };
+  let order = order.clone();
+
+  // 仓位
+  let mut pnl = Decimal::ZERO;
+  let mut fee_asset = pair.info.settle_asset.clone();
+  let balances = &mut ctx.account.balances;
+  if pair.is_spot() {
+    let position = &mut pair.long;
+    let (base, quote) = (pair.info.base_asset.clone(), pair.info.settle_asset.clone());
+    if order.reduce {
+      let closed = size.min(position.size);
+      pnl = (price - position.price) * closed;
+      position.size -= closed;
+      if position.size.is_zero() {
+        position.price = Decimal::ZERO;
+      }
+      *balances.entry(base).or_default() -= size;
+      *balances.entry(quote).or_default() += price * size - fee;
+    } else {
+      let received = match ctx.config.spot_fee {
+        FeeAsset::Received => {
+          fee_asset = base.clone();
+          size - fee / price
+        },
+        FeeAsset::Quote => size,
+      };
+      let paid = match ctx.config.spot_fee {
+        FeeAsset::Received => price * size,
+        FeeAsset::Quote => price * size + fee,
+      };
+      position.price = (position.price * position.size + price * size) / (position.size + received);
+      position.size += received;
+      *balances.entry(base).or_default() += received;
+      *balances.entry(quote).or_default() -= paid;
+    }
+    position.available_size = position.size;
+  } else {
+    let position = match order.side {
+      Side::Long => &mut pair.long,
+      Side::Short => &mut pair.short,
+    };
+    if order.reduce {
+      let size = size.min(position.size);
+      pnl = pair.info.pnl(position.side, position.price, price, size);
+      if !position.size.is_zero() {
+        position.margin -= position.margin * size / position.size;
+      }
+      position.size -= size;
+      if position.size.is_zero() {
+        position.price = Decimal::ZERO;
+        position.margin = Decimal::ZERO;
+      }
+    } else {
+      position.price = pair.info.avg_price(position.price, position.size, price, size);
+      position.size += size;
+      position.available_size += size;
+      position.margin += pair.info.notional(price, size) / order.leverage;
+    }
+    *balances.entry(pair.info.settle_asset.clone()).or_default() += pnl - fee;
+  }
+
+  let trade = Trade {
+    symbol: symbol.to_owned(),
+    order_id: id.to_owned(),
+    time,
+    side: order.side,
+    reduce: order.reduce,
+    price,
+    size,
+    fee,
+    fee_asset,
+    pnl,
+    settle_price: settle_price.unwrap_or_default(),
+    maker,
+    intrabar,
+  };
+  ctx.trades.push(trade.clone());
+  ctx.events.push(Event::Trade(trade));
+  ctx.events.push(Event::Order(order));
+
+  ctx.refresh_account();
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    helpers::date::str_to_date,
+    models::{AggTrade, Candle, Config, Context, Order, Pair, SymbolInfo},
+    types::{FeeAsset, Market, Mode, OrderStatus, Replay, Side, Type},
+  };
+  use anyhow::Result;
+  use chrono::Duration;
+  use rust_decimal::Decimal;
+
+  fn limit(ctx: &Context, symbol: &str, side: Side, size: Decimal, price: Decimal) -> Order {
+    Order {
+      symbol: symbol.to_owned(),
+      id: crate::helpers::id::gen(),
+      r#type: Type::Limit,
+      side,
+      reduce: false,
+      leverage: ctx.pairs[symbol].leverage,
+      size,
+      price,
+      time: ctx.trade_time,
+      margin: Decimal::ZERO,
+      deal_size: Decimal::ZERO,
+      deal_price: Decimal::ZERO,
+      deal_fee: Decimal::ZERO,
+      status: OrderStatus::Submited,
+      amends: vec![],
+      reason: None,
+      strategy: ctx.name.clone(),
+      queue: None,
+    }
+  }
+
+  #[test]
+  fn tests() -> Result<()> {
+    let d = |v: &str| v.parse::<Decimal>().unwrap();
+    let time = str_to_date("202402070537")?;
+    let db = crate::helpers::database::open_temp()?;
+
+    // 同价位多个挂单按盘口排队成交, 合计不超过成交数量
+    let config = Config { replay: Replay::Depth, ..Default::default() };
+    let mut ctx = Context::new("test", Mode::Backtest, db, config, time);
+    let info = SymbolInfo { symbol: "BTCUSDT".to_owned(), ..Default::default() };
+    let mut pair = Pair::new(info, &ctx.config);
+    pair.mark_price = d("105");
+    ctx.pairs.insert("BTCUSDT".to_owned(), pair);
+    let mut ids = vec![];
+    for (i, ahead) in ["3", "5"].into_iter().enumerate() {
+      let mut order = limit(&ctx, "BTCUSDT", Side::Long, d("4"), d("100"));
+      order.time += Duration::seconds(i as i64);
+      let id = super::place_order(&mut ctx, order)?.id;
+      if let Some(order) = ctx.pairs.get_mut("BTCUSDT").and_then(|v| v.orders.get_mut(&id)) {
+        order.queue = Some(d(ahead));
+      }
+      ids.push(id);
+    }
+    let trade = AggTrade {
+      id: 1,
+      price: 100.0,
+      size: 10.0,
+      time: time.timestamp_millis(),
+      buyer_maker: true,
+    };
+    super::match_agg_trade(&mut ctx, "BTCUSDT", &trade)?;
+    let orders = &ctx.pairs["BTCUSDT"].orders;
+    assert_eq!(orders[&ids[0]].deal_size, d("4"));
+    assert_eq!(orders[&ids[1]].deal_size, d("1"));
+
+    // 成交价穿过挂单价时全部成交, 不受该笔成交数量限制
+    let config = Config { replay: Replay::AggTrade, ..Default::default() };
+    let db = crate::helpers::database::open_temp()?;
+    let mut ctx = Context::new("test", Mode::Backtest, db, config, time);
+    let info = SymbolInfo { symbol: "BTCUSDT".to_owned(), ..Default::default() };
+    let mut pair = Pair::new(info, &ctx.config);
+    pair.mark_price = d("105");
+    ctx.pairs.insert("BTCUSDT".to_owned(), pair);
+    let order = limit(&ctx, "BTCUSDT", Side::Long, d("4"), d("100"));
+    let id = super::place_order(&mut ctx, order)?.id;
+    let trade = AggTrade { price: 99.0, size: 1.0, ..trade };
+    super::match_agg_trade(&mut ctx, "BTCUSDT", &trade)?;
+    let order = &ctx.pairs["BTCUSDT"].orders[&id];
+    assert_eq!(order.status, OrderStatus::Completed);
+    assert_eq!(order.deal_size, d("4"));
+
+    // 现货买单手续费从计价资产扣除时冻结手续费, 跳空成交资金不足时拒绝
+    let config = Config { cash: d("1000"), spot_fee: FeeAsset::Quote, ..Default::default() };
+    let db = crate::helpers::database::open_temp()?;
+    let mut ctx = Context::new("test", Mode::Backtest, db, config, time);
+    let info = SymbolInfo {
+      symbol: "BTCUSDT".to_owned(),
+      market: Market::Spot,
+      base_asset: "BTC".to_owned(),
+      ..Default::default()
+    };
+    ctx.pairs.insert("BTCUSDT".to_owned(), Pair::new(info, &ctx.config));
+    let order = limit(&ctx, "BTCUSDT", Side::Long, d("10"), d("100"));
+    let order = super::place_order(&mut ctx, order)?;
+    assert_eq!(order.status, OrderStatus::Rejected);
+    let mut order = limit(&ctx, "BTCUSDT", Side::Long, d("9.9"), d("100"));
+    order.r#type = Type::Market;
+    let order = super::place_order(&mut ctx, order)?;
+    assert_eq!(order.status, OrderStatus::Pending);
+    assert_eq!(order.margin, d("990.495"));
+    let candle = Candle {
+      time: time.timestamp_millis(),
+      open: 110.0,
+      high: 110.0,
+      low: 110.0,
+      close: 110.0,
+      volume: 10.0,
+      amount: 1100.0,
+      trades: 10,
+      taker_volume: 5.0,
+      taker_amount: 550.0,
+    };
+    super::match_candle(&mut ctx, "BTCUSDT", &candle)?;
+    let order = &ctx.pairs["BTCUSDT"].orders[&order.id];
+    assert_eq!(order.status, OrderStatus::Rejected);
+    assert_eq!(ctx.account.balances["USDT"], d("1000"));
+    Ok(())
+  }
+}
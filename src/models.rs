@@ -1,39 +1,103 @@
-use std::collections::HashMap;
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, time::SystemTime};
 
 use crate::{
-  helpers::database::Database,
-  types::{OrderStatus, Side, Type},
+  book::OrderBook,
+  helpers::{
+    constants::{CASH_ASSET, STABLECOINS},
+    database::Database,
+  },
+  monte_carlo::MonteCarlo,
+  risk::RiskState,
+  types::{
+    FeeAsset, Intrabar, MarginMode, Market, Mode, OrderStatus, Replay, Side, TimeFrame, Type,
+  },
 };
-use anyhow::{bail, Result};
-use bincode::{Decode, Encode};
-use chrono::prelude::*;
-use pyo3::prelude::*;
+use anyhow::{bail, ensure, Result};
+use bincode::{
+  de::Decoder,
+  enc::Encoder,
+  error::{DecodeError, EncodeError},
+  Decode, Encode,
+};
+use chrono::{prelude::*, Duration};
+use pyo3::{prelude::*, types::PyTuple};
+use rand::Rng;
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// 策略回调函数
+pub struct Callback {
+  call: Py<PyAny>,
+  /// 是否接收参数, 不接收参数时以无参数调用
+  args: bool,
+}
+
+impl Callback {
+  fn call<A>(&self, args: A) -> Result<()>
+  where
+    A: IntoPy<Py<PyTuple>>,
+  {
+    Python::with_gil(|py| {
+      let call = self.call.bind(py);
+      if self.args {
+        call.call1(args)?;
+      } else {
+        call.call0()?;
+      }
+      anyhow::Ok(())
+    })
+  }
+
+  /// 是否接收参数
+  fn has_args(&self) -> bool {
+    self.args
+  }
+}
 
 /// 策略回调
 pub struct StrategyCallback {
+  /// 策略文件
+  path: String,
+  /// 策略文件修改时间
+  modified: Mutex<Option<SystemTime>>,
+  /// 策略模块
+  module: Py<PyModule>,
   /// 初始化
-  on_init: Option<Py<PyAny>>,
+  on_init: Option<Callback>,
   /// 每日开始
-  on_day_begin: Option<Py<PyAny>>,
+  on_day_begin: Option<Callback>,
   /// 每小时开始
-  on_hour_begin: Option<Py<PyAny>>,
+  on_hour_begin: Option<Callback>,
   /// 每分钟开始
-  on_minute_begin: Option<Py<PyAny>>,
+  on_minute_begin: Option<Callback>,
   /// 每分钟结束
-  on_minute_end: Option<Py<PyAny>>,
+  on_minute_end: Option<Callback>,
   /// 每小时结束
-  on_hour_end: Option<Py<PyAny>>,
+  on_hour_end: Option<Callback>,
   /// 每天结束
-  on_day_end: Option<Py<PyAny>>,
+  on_day_end: Option<Callback>,
   /// tick
-  on_tick: Option<Py<PyAny>>,
+  on_tick: Option<Callback>,
+  /// 交易对池变化
+  on_universe_change: Option<Callback>,
+  /// K线收盘
+  on_bar: Option<Callback>,
+  /// 订单状态变化
+  on_order: Option<Callback>,
+  /// 订单成交
+  on_trade: Option<Callback>,
+  /// 热重载
+  on_reload: Option<Callback>,
   /// 停止运行
-  on_stop: Option<Py<PyAny>>,
+  on_stop: Option<Callback>,
 }
 
 impl StrategyCallback {
-  pub fn new(strategy: &str) -> Result<Self> {
+  /// 编译策略文件
+  /// ---
+  /// `params` 为注入策略模块的参数, 值为JSON, 覆盖模块中的同名变量
+  pub fn new(strategy: &str, params: &[(String, String)]) -> Result<Self> {
     Python::with_gil(|py| {
       let code = std::fs::read_to_string(strategy)?;
       let module = PyModule::from_code_bound(
@@ -42,37 +106,89 @@ impl StrategyCallback {
         strategy,
         format!("_{}", crate::helpers::id::gen()).as_str(),
       )?;
-      anyhow::Ok(Self {
-        on_init: Self::get_call(&module, "on_init")?,
-        on_day_begin: Self::get_call(&module, "on_day_begin")?,
-        on_hour_begin: Self::get_call(&module, "on_hour_begin")?,
-        on_minute_begin: Self::get_call(&module, "on_minute_begin")?,
-        on_minute_end: Self::get_call(&module, "on_minute_end")?,
-        on_hour_end: Self::get_call(&module, "on_hour_end")?,
-        on_day_end: Self::get_call(&module, "on_day_end")?,
-        on_tick: Self::get_call(&module, "on_tick")?,
-        on_stop: Self::get_call(&module, "on_stop")?,
-      })
+      let json = py.import_bound("json")?;
+      for (key, value) in params {
+        module.setattr(key.as_str(), json.call_method1("loads", (value,))?)?;
+      }
+      Self::from_module(strategy, &module)
     })
   }
 
-  fn get_call(module: &Bound<'_, PyModule>, method: &str) -> Result<Option<Py<PyAny>>> {
-    if module.hasattr(method)? {
-      Ok(Some(module.getattr(method)?.unbind()))
-    } else {
-      Ok(None)
+  /// 重新编译策略文件
+  /// ---
+  /// 旧模块中的变量(非函数、类及模块)复制到新模块, 保留策略运行状态
+  pub fn reload(&self) -> Result<Self> {
+    Python::with_gil(|py| {
+      let code = std::fs::read_to_string(&self.path)?;
+      let module = PyModule::from_code_bound(
+        py,
+        &code,
+        &self.path,
+        format!("_{}", crate::helpers::id::gen()).as_str(),
+      )?;
+      let inspect = py.import_bound("inspect")?;
+      for (key, value) in self.module.bind(py).dict().iter() {
+        let name = key.to_string();
+        if name.starts_with('_')
+          || value.is_callable()
+          || inspect.call_method1("ismodule", (&value,))?.is_truthy()?
+        {
+          continue;
+        }
+        module.setattr(name.as_str(), value)?;
+      }
+      Self::from_module(&self.path, &module)
+    })
+  }
+
+  /// 策略文件是否已修改, 检查后记录最新修改时间
+  pub fn is_modified(&self) -> bool {
+    let modified = std::fs::metadata(&self.path).and_then(|v| v.modified()).ok();
+    let mut last = self.modified.lock();
+    if modified == *last {
+      return false;
+    }
+    *last = modified;
+    true
+  }
+
+  fn from_module(strategy: &str, module: &Bound<'_, PyModule>) -> Result<Self> {
+    Ok(Self {
+      path: strategy.to_owned(),
+      modified: Mutex::new(std::fs::metadata(strategy).and_then(|v| v.modified()).ok()),
+      module: module.clone().unbind(),
+      on_init: Self::get_call(module, "on_init")?,
+      on_day_begin: Self::get_call(module, "on_day_begin")?,
+      on_hour_begin: Self::get_call(module, "on_hour_begin")?,
+      on_minute_begin: Self::get_call(module, "on_minute_begin")?,
+      on_minute_end: Self::get_call(module, "on_minute_end")?,
+      on_hour_end: Self::get_call(module, "on_hour_end")?,
+      on_day_end: Self::get_call(module, "on_day_end")?,
+      on_tick: Self::get_call(module, "on_tick")?,
+      on_universe_change: Self::get_call(module, "on_universe_change")?,
+      on_bar: Self::get_call(module, "on_bar")?,
+      on_order: Self::get_call(module, "on_order")?,
+      on_trade: Self::get_call(module, "on_trade")?,
+      on_reload: Self::get_call(module, "on_reload")?,
+      on_stop: Self::get_call(module, "on_stop")?,
+    })
+  }
+
+  fn get_call(module: &Bound<'_, PyModule>, method: &str) -> Result<Option<Callback>> {
+    if !module.hasattr(method)? {
+      return Ok(None);
     }
+    let call = module.getattr(method)?;
+    let inspect = module.py().import_bound("inspect")?;
+    let params = inspect.call_method1("signature", (&call,))?.getattr("parameters")?;
+    Ok(Some(Callback { call: call.unbind(), args: params.len()? > 0 }))
   }
 }
 
 impl StrategyCallback {
   pub fn on_init(&self) -> Result<()> {
     if let Some(call) = &self.on_init {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     } else {
       bail!("未定义初始化事件");
@@ -80,33 +196,21 @@ impl StrategyCallback {
   }
   pub fn on_day_begin(&self) -> Result<()> {
     if let Some(call) = &self.on_day_begin {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
   pub fn on_hour_begin(&self) -> Result<()> {
     if let Some(call) = &self.on_hour_begin {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
   pub fn on_minute_begin(&self) -> Result<()> {
     if let Some(call) = &self.on_minute_begin {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
@@ -114,55 +218,77 @@ impl StrategyCallback {
 
   pub fn on_minute_end(&self) -> Result<()> {
     if let Some(call) = &self.on_minute_end {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
   pub fn on_hour_end(&self) -> Result<()> {
     if let Some(call) = &self.on_hour_end {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
   pub fn on_day_end(&self) -> Result<()> {
     if let Some(call) = &self.on_day_end {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
-  pub fn on_tick(&self) -> Result<()> {
+  /// 接收参数时逐个交易对调用 `on_tick(tick)`, 否则调用一次 `on_tick()`
+  pub fn on_tick(&self, ticks: Vec<Tick>) -> Result<()> {
     if let Some(call) = &self.on_tick {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      if call.has_args() {
+        for tick in ticks {
+          call.call((tick,))?;
+        }
+      } else {
+        call.call(())?;
+      }
+      return Ok(());
+    }
+    Ok(())
+  }
+  pub fn on_universe_change(&self, added: Vec<String>, removed: Vec<String>) -> Result<()> {
+    if let Some(call) = &self.on_universe_change {
+      call.call((added, removed))?;
+      return Ok(());
+    }
+    Ok(())
+  }
+  pub fn on_bar(&self, symbol: &str, timeframe: TimeFrame, candle: Candle) -> Result<()> {
+    if let Some(call) = &self.on_bar {
+      call.call((symbol, timeframe, candle))?;
+      return Ok(());
+    }
+    Ok(())
+  }
+  pub fn on_order(&self, order: Order) -> Result<()> {
+    if let Some(call) = &self.on_order {
+      call.call((order,))?;
+      return Ok(());
+    }
+    Ok(())
+  }
+  pub fn on_trade(&self, trade: Trade) -> Result<()> {
+    if let Some(call) = &self.on_trade {
+      call.call((trade,))?;
+      return Ok(());
+    }
+    Ok(())
+  }
+  pub fn on_reload(&self) -> Result<()> {
+    if let Some(call) = &self.on_reload {
+      call.call(())?;
       return Ok(());
     }
     Ok(())
   }
   pub fn on_stop(&self) -> Result<()> {
     if let Some(call) = &self.on_stop {
-      Python::with_gil(|py| {
-        let call = call.bind(py);
-        call.call0()?;
-        anyhow::Ok(())
-      })?;
+      call.call(())?;
       return Ok(());
     }
     Ok(())
@@ -170,6 +296,7 @@ impl StrategyCallback {
 }
 
 /// K线
+#[pyclass(get_all)]
 #[derive(Debug, Clone, Encode, Decode)]
 pub struct Candle {
   /// 开盘时间
@@ -194,6 +321,151 @@ pub struct Candle {
   pub trades: i64,
 }
 
+impl Candle {
+  /// 按首笔成交开始K线
+  pub fn from_agg_trade(time: i64, trade: &AggTrade) -> Self {
+    let mut candle = Self {
+      time,
+      open: trade.price,
+      high: trade.price,
+      low: trade.price,
+      close: trade.price,
+      volume: 0.0,
+      amount: 0.0,
+      taker_volume: 0.0,
+      taker_amount: 0.0,
+      trades: 0,
+    };
+    candle.push_agg_trade(trade);
+    candle
+  }
+
+  /// 合并一笔成交, 买方吃单时计入吃单数量
+  pub fn push_agg_trade(&mut self, trade: &AggTrade) {
+    let amount = trade.price * trade.size;
+    self.high = self.high.max(trade.price);
+    self.low = self.low.min(trade.price);
+    self.close = trade.price;
+    self.volume += trade.size;
+    self.amount += amount;
+    if !trade.buyer_maker {
+      self.taker_volume += trade.size;
+      self.taker_amount += amount;
+    }
+    self.trades += 1;
+  }
+
+  /// 合并后一根K线
+  pub fn merge(&mut self, candle: &Candle) {
+    self.high = self.high.max(candle.high);
+    self.low = self.low.min(candle.low);
+    self.close = candle.close;
+    self.volume += candle.volume;
+    self.amount += candle.amount;
+    self.taker_volume += candle.taker_volume;
+    self.taker_amount += candle.taker_amount;
+    self.trades += candle.trades;
+  }
+}
+
+/// 归集成交
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct AggTrade {
+  /// 归集成交ID
+  pub id: i64,
+  /// 成交价格
+  pub price: f64,
+  /// 成交数量
+  pub size: f64,
+  /// 成交时间
+  pub time: i64,
+  /// 买方是否为挂单方
+  pub buyer_maker: bool,
+}
+
+/// 盘口更新
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DepthUpdate {
+  /// 时间
+  pub time: i64,
+  /// 最后更新ID
+  pub update_id: i64,
+  /// 是否全量快照
+  pub snapshot: bool,
+  /// 买盘 (价格, 数量), 增量更新中数量为0表示删除该档
+  pub bids: Vec<(f64, f64)>,
+  /// 卖盘 (价格, 数量)
+  pub asks: Vec<(f64, f64)>,
+}
+
+/// 行情事件
+#[pyclass(get_all)]
+#[derive(Debug, Clone)]
+pub struct Tick {
+  pub symbol: String,
+  /// 时间
+  pub time: DateTime<Utc>,
+  /// 最新价格
+  pub price: Decimal,
+  /// 成交数量
+  pub size: Decimal,
+}
+
+/// 成交
+#[pyclass(get_all)]
+#[derive(Debug, Clone)]
+pub struct Trade {
+  pub symbol: String,
+  /// 订单ID
+  pub order_id: String,
+  /// 成交时间
+  pub time: DateTime<Utc>,
+  pub side: Side,
+  /// 是否减仓
+  pub reduce: bool,
+  /// 成交价格
+  pub price: Decimal,
+  /// 成交数量
+  pub size: Decimal,
+  /// 手续费, 以结算资产计
+  pub fee: Decimal,
+  /// 实际扣除手续费的资产
+  pub fee_asset: String,
+  /// 平仓盈亏, 以结算资产计, 开仓为0
+  pub pnl: Decimal,
+  /// 成交时结算资产的价格, 以资金资产计
+  pub settle_price: Decimal,
+  /// 是否挂单成交
+  pub maker: bool,
+  /// 按K线撮合时使用的K线内价格路径假设
+  pub intrabar: Option<Intrabar>,
+}
+
+/// 策略事件, 释放引擎锁后按产生顺序通知策略
+#[derive(Debug, Clone)]
+pub enum Event {
+  Order(Order),
+  Trade(Trade),
+}
+
+/// 延迟到达的订单请求
+#[derive(Debug, Clone)]
+pub enum Ack {
+  /// 下单, 交易对及订单ID
+  Place(String, String),
+  /// 撤单, 交易对及订单ID
+  Cancel(String, String),
+}
+
+/// K线订阅
+#[derive(Debug, Clone)]
+pub struct Subscription {
+  pub symbol: String,
+  pub timeframe: TimeFrame,
+  /// 合成中的K线
+  pub candle: Option<Candle>,
+}
+
 /// 订单
 #[pyclass(get_all)]
 #[derive(Debug, Clone)]
@@ -226,19 +498,372 @@ pub struct Order {
   pub deal_fee: Decimal,
   /// 状态
   pub status: OrderStatus,
+  /// 修改记录
+  pub amends: Vec<OrderAmend>,
+  /// 拒绝原因
+  pub reason: Option<String>,
+  /// 所属策略
+  pub strategy: String,
+  /// 排队位置, 同价位排在前面的挂单数量, 仅按盘口回放时有效
+  pub queue: Option<Decimal>,
+}
+
+impl Order {
+  /// 是否有效订单
+  pub fn is_open(&self) -> bool {
+    matches!(
+      self.status,
+      OrderStatus::Created | OrderStatus::Submited | OrderStatus::Pending | OrderStatus::Partial
+    )
+  }
+
+  /// 是否已被接受, 可参与撮合
+  pub fn is_accepted(&self) -> bool {
+    matches!(self.status, OrderStatus::Pending | OrderStatus::Partial)
+  }
+
+  /// 是否买入 (开多或平空)
+  pub fn is_buy(&self) -> bool {
+    matches!((self.side, self.reduce), (Side::Long, false) | (Side::Short, true))
+  }
+
+  /// 未成交数量
+  pub fn remain_size(&self) -> Decimal {
+    self.size - self.deal_size
+  }
+
+  /// 修改价格或数量, 并记录修改历史
+  pub fn amend(
+    &mut self,
+    time: DateTime<Utc>,
+    price: Option<Decimal>,
+    size: Option<Decimal>,
+  ) -> Result<()> {
+    ensure!(self.is_open(), "订单已结束 : {}", self.id);
+    ensure!(self.r#type == Type::Limit, "仅支持修改限价订单 : {}", self.id);
+    let price = price.unwrap_or(self.price);
+    let size = size.unwrap_or(self.size);
+    ensure!(price.is_sign_positive() && !price.is_zero(), "价格无效 : {price}");
+    ensure!(size > self.deal_size, "数量必须大于已成交数量 : {size} <= {}", self.deal_size);
+    ensure!(price != self.price || size != self.size, "订单无需修改 : {}", self.id);
+    self.amends.push(OrderAmend { time, old_price: self.price, old_size: self.size, price, size });
+    self.price = price;
+    self.size = size;
+    Ok(())
+  }
+}
+
+/// 订单修改记录
+#[pyclass(get_all)]
+#[derive(Debug, Clone)]
+pub struct OrderAmend {
+  /// 修改时间
+  pub time: DateTime<Utc>,
+  /// 修改前价格
+  pub old_price: Decimal,
+  /// 修改前数量
+  pub old_size: Decimal,
+  /// 修改后价格
+  pub price: Decimal,
+  /// 修改后数量
+  pub size: Decimal,
+}
+
+/// 风控限制, 未设置的项不检查
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RiskLimits {
+  /// 单交易对最大名义价值
+  pub max_notional: Option<Decimal>,
+  /// 账户最大总杠杆
+  pub max_leverage: Option<Decimal>,
+  /// 最大有效订单数
+  pub max_open_orders: Option<usize>,
+  /// 每分钟最大下单数
+  pub max_order_rate: Option<usize>,
+  /// 单日最大亏损
+  pub max_daily_loss: Option<Decimal>,
+  /// 限价偏离标记价格的最大比例
+  pub price_band: Option<Decimal>,
+  /// 账户最大回撤比例, 超过则暂停交易
+  pub max_drawdown: Option<Decimal>,
+}
+
+#[pymethods]
+impl RiskLimits {
+  #[new]
+  #[pyo3(signature = (
+    max_notional=None,
+    max_leverage=None,
+    max_open_orders=None,
+    max_order_rate=None,
+    max_daily_loss=None,
+    price_band=None,
+    max_drawdown=None,
+  ))]
+  fn new(
+    max_notional: Option<Decimal>,
+    max_leverage: Option<Decimal>,
+    max_open_orders: Option<usize>,
+    max_order_rate: Option<usize>,
+    max_daily_loss: Option<Decimal>,
+    price_band: Option<Decimal>,
+    max_drawdown: Option<Decimal>,
+  ) -> Self {
+    Self {
+      max_notional,
+      max_leverage,
+      max_open_orders,
+      max_order_rate,
+      max_daily_loss,
+      price_band,
+      max_drawdown,
+    }
+  }
+}
+
+/// 回测订单延迟, 单位毫秒
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Latency {
+  /// 下单延迟
+  pub submit: i64,
+  /// 撤单延迟
+  pub cancel: i64,
+  /// 随机延迟均值, 按指数分布叠加在固定延迟上, 0表示固定延迟
+  pub jitter: i64,
+}
+
+#[pymethods]
+impl Latency {
+  #[new]
+  #[pyo3(signature = (submit=0, cancel=0, jitter=0))]
+  fn new(submit: i64, cancel: i64, jitter: i64) -> Self {
+    Self { submit, cancel, jitter }
+  }
+}
+
+impl Latency {
+  /// 是否有延迟
+  pub fn is_zero(&self) -> bool {
+    self.submit <= 0 && self.cancel <= 0 && self.jitter <= 0
+  }
+
+  /// 按固定延迟抽样, 随机部分由运行随机数生成器生成
+  pub fn sample(&self, base: i64) -> Duration {
+    let jitter = if self.jitter > 0 {
+      let u = crate::helpers::random::with_rng(|rng| rng.gen::<f64>());
+      (-(1.0 - u).ln() * self.jitter as f64) as i64
+    } else {
+      0
+    };
+    Duration::milliseconds(base.max(0) + jitter)
+  }
+}
+
+/// 交易对池规则
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UniverseRule {
+  /// 按24小时成交额取前N个交易对, 未设置则不限
+  pub top: Option<usize>,
+  /// 排除上线不足N天的交易对
+  pub min_listed_days: i64,
+  /// 合约类型, 未设置则不限
+  pub contract_type: Option<String>,
+  /// 市场类型
+  pub market: Market,
+}
+
+#[pymethods]
+impl UniverseRule {
+  #[new]
+  #[pyo3(signature = (top=None, min_listed_days=0, contract_type=None, market=Market::UsdM))]
+  fn new(
+    top: Option<usize>,
+    min_listed_days: i64,
+    contract_type: Option<String>,
+    market: Market,
+  ) -> Self {
+    Self { top, min_listed_days, contract_type, market }
+  }
+}
+
+/// 运行配置
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+  /// 初始资金
+  pub cash: Decimal,
+  /// 默认杠杆倍数
+  pub leverage: Decimal,
+  /// 挂单手续费率
+  pub maker_fee: Decimal,
+  /// 吃单手续费率
+  pub taker_fee: Decimal,
+  /// 回测开始时间
+  pub begin: Option<DateTime<Utc>>,
+  /// 回测结束时间
+  pub end: Option<DateTime<Utc>>,
+  /// 固定交易对
+  pub symbols: Vec<String>,
+  /// 交易对池规则, 每日重新选择
+  pub universe: Option<UniverseRule>,
+  /// 风控限制
+  pub risk: RiskLimits,
+  /// 暂停文件, 文件存在时暂停交易
+  pub halt_file: Option<String>,
+  /// 交易对规则快照, 交易所 `exchangeInfo` 格式的JSON文件
+  pub symbol_file: Option<String>,
+  /// 随机种子, 回测未指定时为0, 模拟及实盘未指定时随机
+  pub seed: Option<u64>,
+  /// 回测行情回放方式
+  pub replay: Replay,
+  /// 按K线撮合时的K线内价格路径假设
+  pub intrabar: Intrabar,
+  /// 回测订单延迟
+  pub latency: Latency,
+  /// 初始钱包余额, 不含初始资金
+  pub balances: BTreeMap<String, Decimal>,
+  /// 联合保证金模式, U本位合约共用抵押资产折算后的保证金
+  pub multi_assets: bool,
+  /// 联合保证金模式下的抵押资产及折价率, 资金资产始终可作抵押且不折价
+  pub haircuts: BTreeMap<String, Decimal>,
+  /// 现货手续费扣除资产
+  pub spot_fee: FeeAsset,
+  /// 默认保证金模式
+  pub margin_mode: MarginMode,
+  /// 维持保证金率, 用于计算强平价格
+  pub maintenance_rate: Decimal,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      cash: Decimal::from(10000),
+      leverage: Decimal::ONE,
+      maker_fee: Decimal::new(2, 4),
+      taker_fee: Decimal::new(5, 4),
+      begin: None,
+      end: None,
+      symbols: vec![],
+      universe: None,
+      risk: Default::default(),
+      halt_file: None,
+      symbol_file: None,
+      seed: None,
+      replay: Replay::Candle,
+      intrabar: Intrabar::OpenHighLowClose,
+      latency: Default::default(),
+      balances: Default::default(),
+      multi_assets: false,
+      haircuts: Default::default(),
+      spot_fee: FeeAsset::Received,
+      margin_mode: MarginMode::Cross,
+      maintenance_rate: Decimal::new(4, 3),
+    }
+  }
+}
+
+#[pymethods]
+impl Config {
+  #[new]
+  #[pyo3(signature = (
+    cash=None,
+    leverage=None,
+    maker_fee=None,
+    taker_fee=None,
+    begin=None,
+    end=None,
+    symbols=vec![],
+    universe=None,
+    risk=None,
+    halt_file=None,
+    symbol_file=None,
+    seed=None,
+    replay=Replay::Candle,
+    intrabar=Intrabar::OpenHighLowClose,
+    latency=None,
+    balances=BTreeMap::new(),
+    multi_assets=false,
+    haircuts=BTreeMap::new(),
+    spot_fee=FeeAsset::Received,
+    margin_mode=MarginMode::Cross,
+    maintenance_rate=None,
+  ))]
+  #[allow(clippy::too_many_arguments)]
+  fn new(
+    cash: Option<Decimal>,
+    leverage: Option<Decimal>,
+    maker_fee: Option<Decimal>,
+    taker_fee: Option<Decimal>,
+    begin: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    symbols: Vec<String>,
+    universe: Option<UniverseRule>,
+    risk: Option<RiskLimits>,
+    halt_file: Option<String>,
+    symbol_file: Option<String>,
+    seed: Option<u64>,
+    replay: Replay,
+    intrabar: Intrabar,
+    latency: Option<Latency>,
+    balances: BTreeMap<String, Decimal>,
+    multi_assets: bool,
+    haircuts: BTreeMap<String, Decimal>,
+    spot_fee: FeeAsset,
+    margin_mode: MarginMode,
+    maintenance_rate: Option<Decimal>,
+  ) -> Self {
+    let default = Self::default();
+    Self {
+      cash: cash.unwrap_or(default.cash),
+      leverage: leverage.unwrap_or(default.leverage),
+      maker_fee: maker_fee.unwrap_or(default.maker_fee),
+      taker_fee: taker_fee.unwrap_or(default.taker_fee),
+      begin,
+      end,
+      symbols,
+      universe,
+      risk: risk.unwrap_or_default(),
+      halt_file,
+      symbol_file,
+      seed,
+      replay,
+      intrabar,
+      latency: latency.unwrap_or_default(),
+      balances,
+      multi_assets,
+      haircuts,
+      spot_fee,
+      margin_mode,
+      maintenance_rate: maintenance_rate.unwrap_or(default.maintenance_rate),
+    }
+  }
 }
 
 /// 账户
 #[derive(Debug, Default, Clone)]
 pub struct Account {
-  /// 资金
+  /// 资金, 钱包余额按标记价格估值
   pub cash: Decimal,
-  /// 可用资金
+  /// 可用资金, U本位合约可用保证金, 联合保证金模式下按抵押资产折算
   pub available_cash: Decimal,
   /// 保证金
   pub margin: Decimal,
   /// 未实现盈亏
   pub pnl: Decimal,
+  /// 钱包余额
+  pub balances: BTreeMap<String, Decimal>,
+  /// 可用余额, 扣除现货冻结, 按结算资产计入合约盈亏、保证金及冻结
+  pub available_balances: BTreeMap<String, Decimal>,
+}
+
+impl Account {
+  /// 权益, 资金及未实现盈亏之和
+  pub fn equity(&self) -> Decimal {
+    self.cash + self.pnl
+  }
 }
 
 /// 仓位
@@ -262,30 +887,671 @@ pub struct Position {
   pub margin: Decimal,
   /// 未实现盈亏
   pub pnl: Decimal,
+  /// 强平价格, 0表示不会强平
+  pub liquidation_price: Decimal,
 }
 
+impl Position {
+  pub fn new(symbol: &str, side: Side, leverage: Decimal) -> Self {
+    Self {
+      symbol: symbol.to_owned(),
+      side,
+      leverage,
+      mark_price: Decimal::ZERO,
+      size: Decimal::ZERO,
+      available_size: Decimal::ZERO,
+      price: Decimal::ZERO,
+      margin: Decimal::ZERO,
+      pnl: Decimal::ZERO,
+      liquidation_price: Decimal::ZERO,
+    }
+  }
+
+  /// 标记价格是否触及强平价格
+  pub fn is_liquidated(&self, price: Decimal) -> bool {
+    if self.size.is_zero() || self.liquidation_price.is_zero() {
+      return false;
+    }
+    match self.side {
+      Side::Long => price <= self.liquidation_price,
+      Side::Short => price >= self.liquidation_price,
+    }
+  }
+}
+
+/// 交易对规则
+#[pyclass(get_all)]
+#[derive(Debug, Default, Clone)]
+pub struct SymbolInfo {
+  /// 交易对, 现货为 `基础资产/计价资产`
+  pub symbol: String,
+  /// 市场类型
+  pub market: Market,
+  /// 基础资产
+  pub base_asset: String,
+  /// 计价资产
+  pub quote_asset: String,
+  /// 结算资产, 保证金、手续费及盈亏以此计, 现货为计价资产, 未知时为资金资产
+  pub settle_asset: String,
+  /// 合约面值, 仅币本位合约, 以计价资产计
+  pub contract_size: Decimal,
+  /// 价格最小变动单位
+  pub tick_size: Decimal,
+  /// 数量步长
+  pub step_size: Decimal,
+  /// 最小数量
+  pub min_size: Decimal,
+  /// 最大数量
+  pub max_size: Decimal,
+  /// 最小名义价值, 以结算资产计
+  pub min_notional: Decimal,
+  /// 最大杠杆倍数, 0表示未知
+  pub max_leverage: Decimal,
+  /// 杠杆分层, (名义价值上限, 最大杠杆倍数), 按名义价值升序, 空表示未知
+  pub brackets: Vec<(Decimal, Decimal)>,
+  /// 合约类型
+  pub contract_type: String,
+  /// 上线时间
+  pub onboard_date: i64,
+  /// 下线时间
+  pub delist_date: i64,
+}
+
+impl SymbolInfo {
+  /// 交易所交易对名称
+  pub fn exchange_symbol(&self) -> String {
+    match self.market {
+      Market::Spot => format!("{}{}", self.base_asset, self.quote_asset),
+      Market::UsdM | Market::CoinM => self.symbol.clone(),
+    }
+  }
+
+  /// 是否币本位合约
+  pub fn is_inverse(&self) -> bool {
+    self.market == Market::CoinM
+  }
+
+  /// 单位数量的计价名义价值, 币本位合约为合约面值
+  pub fn unit_value(&self, price: Decimal) -> Decimal {
+    if self.is_inverse() {
+      self.contract_size
+    } else {
+      price
+    }
+  }
+
+  /// 名义价值, 以结算资产计
+  /// ---
+  /// 币本位合约为 `数量 * 面值 / 价格`
+  pub fn notional(&self, price: Decimal, size: Decimal) -> Decimal {
+    if !self.is_inverse() {
+      price * size
+    } else if price.is_zero() {
+      Decimal::ZERO
+    } else {
+      size * self.contract_size / price
+    }
+  }
+
+  /// 盈亏, 以结算资产计
+  /// ---
+  /// 币本位合约做多为 `数量 * 面值 * (1 / 开仓价 - 1 / 平仓价)`
+  pub fn pnl(&self, side: Side, entry: Decimal, exit: Decimal, size: Decimal) -> Decimal {
+    let pnl = if !self.is_inverse() {
+      (exit - entry) * size
+    } else if entry.is_zero() || exit.is_zero() {
+      Decimal::ZERO
+    } else {
+      size * self.contract_size * (Decimal::ONE / entry - Decimal::ONE / exit)
+    };
+    match side {
+      Side::Long => pnl,
+      Side::Short => -pnl,
+    }
+  }
+
+  /// 强平价格, 保证金扣除亏损后等于维持保证金时的价格, 0表示不会强平
+  /// ---
+  /// - U本位做多 `(开仓价 * 数量 - 保证金) / (数量 * (1 - 维持保证金率))`
+  /// - 币本位做多 `面值 * 数量 * (1 + 维持保证金率) / (保证金 + 面值 * 数量 / 开仓价)`
+  pub fn liquidation_price(
+    &self,
+    side: Side,
+    entry: Decimal,
+    size: Decimal,
+    margin: Decimal,
+    rate: Decimal,
+  ) -> Decimal {
+    if size.is_zero() || entry.is_zero() {
+      return Decimal::ZERO;
+    }
+    let (numerator, denominator) = match (self.is_inverse(), side) {
+      (false, Side::Long) => (entry * size - margin, size * (Decimal::ONE - rate)),
+      (false, Side::Short) => (entry * size + margin, size * (Decimal::ONE + rate)),
+      (true, Side::Long) => {
+        let value = size * self.contract_size;
+        (value * (Decimal::ONE + rate), margin + value / entry)
+      },
+      (true, Side::Short) => {
+        let value = size * self.contract_size;
+        (value * (Decimal::ONE - rate), value / entry - margin)
+      },
+    };
+    if numerator <= Decimal::ZERO || denominator <= Decimal::ZERO {
+      return Decimal::ZERO;
+    }
+    (numerator / denominator).max(Decimal::ZERO)
+  }
+
+  /// 名义价值对应的最大杠杆倍数, 0表示未知
+  /// ---
+  /// 超过最高分层时取最高分层
+  pub fn bracket_leverage(&self, notional: Decimal) -> Decimal {
+    self
+      .brackets
+      .iter()
+      .find(|v| notional <= v.0)
+      .or(self.brackets.last())
+      .map(|v| v.1)
+      .unwrap_or(self.max_leverage)
+  }
+
+  /// 加仓后均价, 币本位合约按调和平均
+  pub fn avg_price(
+    &self,
+    price: Decimal,
+    size: Decimal,
+    add_price: Decimal,
+    add_size: Decimal,
+  ) -> Decimal {
+    if size.is_zero() || price.is_zero() {
+      return add_price;
+    }
+    if self.is_inverse() {
+      (size + add_size) / (size / price + add_size / add_price)
+    } else {
+      (price * size + add_price * add_size) / (size + add_size)
+    }
+  }
+
+  /// 指定时间是否可交易
+  pub fn is_listed(&self, time: i64) -> bool {
+    self.onboard_date <= time && (self.delist_date == 0 || time < self.delist_date)
+  }
+
+  /// 价格按最小变动单位四舍五入
+  pub fn round_price(&self, price: Decimal) -> Decimal {
+    if self.tick_size.is_zero() {
+      return price;
+    }
+    ((price / self.tick_size).round() * self.tick_size).normalize()
+  }
+
+  /// 数量按步长向下取整
+  /// ---
+  /// - 超过最大数量时取最大数量
+  /// - 小于最小数量时返回0
+  pub fn round_size(&self, size: Decimal) -> Decimal {
+    let mut size = if self.step_size.is_zero() {
+      size
+    } else {
+      (size / self.step_size).floor() * self.step_size
+    };
+    if !self.max_size.is_zero() {
+      size = size.min(self.max_size);
+    }
+    if size.is_sign_negative() || size < self.min_size {
+      return Decimal::ZERO;
+    }
+    size.normalize()
+  }
+
+  /// 检查订单价格及数量是否符合规则
+  pub fn check_order(&self, order: &Order) -> Result<()> {
+    if order.r#type == Type::Limit && !self.tick_size.is_zero() {
+      ensure!(
+        (order.price % self.tick_size).is_zero(),
+        "价格不符合最小变动单位 : {} / {}",
+        order.price,
+        self.tick_size
+      );
+    }
+    if !self.step_size.is_zero() {
+      ensure!(
+        (order.size % self.step_size).is_zero(),
+        "数量不符合步长 : {} / {}",
+        order.size,
+        self.step_size
+      );
+    }
+    ensure!(order.size >= self.min_size, "数量小于最小数量 : {} < {}", order.size, self.min_size);
+    if !self.max_size.is_zero() {
+      ensure!(order.size <= self.max_size, "数量大于最大数量 : {} > {}", order.size, self.max_size);
+    }
+    if !order.reduce {
+      let notional = self.notional(order.price, order.size);
+      ensure!(
+        notional >= self.min_notional,
+        "名义价值小于最小名义价值 : {notional} < {}",
+        self.min_notional
+      );
+    }
+    Ok(())
+  }
+}
+
+impl Encode for SymbolInfo {
+  fn encode<E: Encoder>(&self, encoder: &mut E) -> std::result::Result<(), EncodeError> {
+    self.symbol.encode(encoder)?;
+    (self.market as u8).encode(encoder)?;
+    self.base_asset.encode(encoder)?;
+    self.quote_asset.encode(encoder)?;
+    self.settle_asset.encode(encoder)?;
+    self.contract_size.serialize().encode(encoder)?;
+    self.tick_size.serialize().encode(encoder)?;
+    self.step_size.serialize().encode(encoder)?;
+    self.min_size.serialize().encode(encoder)?;
+    self.max_size.serialize().encode(encoder)?;
+    self.min_notional.serialize().encode(encoder)?;
+    self.max_leverage.serialize().encode(encoder)?;
+    self
+      .brackets
+      .iter()
+      .map(|v| (v.0.serialize(), v.1.serialize()))
+      .collect::<Vec<_>>()
+      .encode(encoder)?;
+    self.contract_type.encode(encoder)?;
+    self.onboard_date.encode(encoder)?;
+    self.delist_date.encode(encoder)?;
+    Ok(())
+  }
+}
+
+impl Decode for SymbolInfo {
+  fn decode<D: Decoder>(decoder: &mut D) -> std::result::Result<Self, DecodeError> {
+    Ok(Self {
+      symbol: Decode::decode(decoder)?,
+      market: Market::from_repr(Decode::decode(decoder)?).unwrap_or_default(),
+      base_asset: Decode::decode(decoder)?,
+      quote_asset: Decode::decode(decoder)?,
+      settle_asset: Decode::decode(decoder)?,
+      contract_size: Decimal::deserialize(Decode::decode(decoder)?),
+      tick_size: Decimal::deserialize(Decode::decode(decoder)?),
+      step_size: Decimal::deserialize(Decode::decode(decoder)?),
+      min_size: Decimal::deserialize(Decode::decode(decoder)?),
+      max_size: Decimal::deserialize(Decode::decode(decoder)?),
+      min_notional: Decimal::deserialize(Decode::decode(decoder)?),
+      max_leverage: Decimal::deserialize(Decode::decode(decoder)?),
+      brackets: Vec::<([u8; 16], [u8; 16])>::decode(decoder)?
+        .into_iter()
+        .map(|v| (Decimal::deserialize(v.0), Decimal::deserialize(v.1)))
+        .collect(),
+      contract_type: Decode::decode(decoder)?,
+      onboard_date: Decode::decode(decoder)?,
+      delist_date: Decode::decode(decoder)?,
+    })
+  }
+}
+
+#[derive(Clone)]
 pub struct Pair {
   pub symbol: String,
+  /// 交易对规则
+  pub info: SymbolInfo,
   pub leverage: Decimal,
   pub margin: Decimal,
   pub mark_price: Decimal,
+  /// 挂单手续费率
+  pub maker_fee: Decimal,
+  /// 吃单手续费率
+  pub taker_fee: Decimal,
+  /// 保证金模式
+  pub margin_mode: MarginMode,
   pub long: Position,
   pub short: Position,
-  pub orders: HashMap<String, Order>,
+  pub orders: BTreeMap<String, Order>,
+}
+
+impl Pair {
+  pub fn new(mut info: SymbolInfo, config: &Config) -> Self {
+    if info.settle_asset.is_empty() {
+      info.settle_asset = CASH_ASSET.to_owned();
+    }
+    let leverage = if info.market == Market::Spot {
+      Decimal::ONE
+    } else if info.max_leverage.is_zero() {
+      config.leverage
+    } else {
+      config.leverage.min(info.max_leverage)
+    };
+    Self {
+      symbol: info.symbol.clone(),
+      long: Position::new(&info.symbol, Side::Long, leverage),
+      short: Position::new(&info.symbol, Side::Short, leverage),
+      info,
+      leverage,
+      margin: Decimal::ZERO,
+      mark_price: Decimal::ZERO,
+      maker_fee: config.maker_fee,
+      taker_fee: config.taker_fee,
+      margin_mode: config.margin_mode,
+      orders: BTreeMap::new(),
+    }
+  }
+
+  /// 是否现货
+  pub fn is_spot(&self) -> bool {
+    self.info.market == Market::Spot
+  }
+
+  /// 是否币本位合约
+  pub fn is_inverse(&self) -> bool {
+    self.info.is_inverse()
+  }
+
+  /// 是否逐仓
+  pub fn is_isolated(&self) -> bool {
+    self.margin_mode == MarginMode::Isolated
+  }
+
+  /// 是否无持仓且无有效订单
+  pub fn is_idle(&self) -> bool {
+    self.long.size.is_zero()
+      && self.short.size.is_zero()
+      && !self.orders.values().any(|o| o.is_open())
+  }
 }
 
-/// 上下文
+/// 上下文, 每个策略独立
 pub struct Context {
+  /// 策略名称
+  pub name: String,
+  /// 运行模式
+  pub mode: Mode,
   /// 数据库
   pub db: Database,
+  /// 运行配置
+  pub config: Config,
+  /// 风控状态
+  pub risk: RiskState,
+  /// 交易暂停原因
+  pub halted: Option<String>,
   /// 是否运行中
   pub running: bool,
   /// 交易时间
   pub trade_time: DateTime<Utc>,
+  /// 模拟时钟, 回测中按此计算订单请求到达时间
+  pub clock: DateTime<Utc>,
   /// 基准
   pub benchmark: String,
   /// 账户
   pub account: Account,
   /// 交易对
-  pub pairs: HashMap<String, Pair>,
+  pub pairs: BTreeMap<String, Pair>,
+  /// 当前K线
+  pub candles: BTreeMap<String, Candle>,
+  /// 订单簿, 仅按盘口回放时重建
+  pub books: BTreeMap<String, OrderBook>,
+  /// K线订阅
+  pub subscriptions: Vec<Subscription>,
+  /// 待通知策略的事件
+  pub events: Vec<Event>,
+  /// 尚未到达的订单请求, 按到达时间排序
+  pub acks: Vec<(DateTime<Utc>, Ack)>,
+  /// 成交记录
+  pub trades: Vec<Trade>,
+  /// 权益曲线, 每分钟结束时记录
+  pub equity: Vec<(DateTime<Utc>, Decimal)>,
+  /// 初始权益, 资金加上初始钱包余额按首个权益点时的价格估值
+  pub initial_equity: Option<Decimal>,
+  /// 最近一次蒙特卡洛分析
+  pub monte_carlo: Option<MonteCarlo>,
+}
+
+impl Context {
+  /// 创建上下文, 初始资产为 `Config::balances` 加上资金
+  pub fn new(
+    name: &str,
+    mode: Mode,
+    db: Database,
+    config: Config,
+    trade_time: DateTime<Utc>,
+  ) -> Self {
+    let mut balances = config.balances.clone();
+    *balances.entry(CASH_ASSET.to_owned()).or_default() += config.cash;
+    let mut ctx = Self {
+      name: name.to_owned(),
+      mode,
+      db,
+      risk: Default::default(),
+      halted: None,
+      running: false,
+      trade_time,
+      clock: trade_time,
+      benchmark: Default::default(),
+      account: Account { balances, ..Default::default() },
+      pairs: Default::default(),
+      candles: Default::default(),
+      books: Default::default(),
+      subscriptions: Default::default(),
+      events: Default::default(),
+      acks: Default::default(),
+      trades: Default::default(),
+      equity: Default::default(),
+      initial_equity: None,
+      monte_carlo: None,
+      config,
+    };
+    ctx.refresh_account();
+    let equity = ctx.account.equity();
+    ctx.risk.roll_day(trade_time, equity);
+    ctx
+  }
+
+  /// 资产价格
+  /// ---
+  /// - 资金资产为1
+  /// - 取以该资产为基础资产、稳定币计价的交易对标记价格, 现货优先, 作为币本位合约折算的指数价格
+  /// - 没有交易对时稳定币为1, 其他为0
+  pub fn asset_price(&self, asset: &str) -> Decimal {
+    if asset == CASH_ASSET {
+      return Decimal::ONE;
+    }
+    self
+      .pairs
+      .values()
+      .filter(|v| v.info.base_asset == asset && STABLECOINS.contains(&v.info.quote_asset.as_str()))
+      .filter(|v| !v.mark_price.is_zero())
+      .max_by_key(|v| v.is_spot())
+      .map(|v| v.mark_price)
+      .unwrap_or(if STABLECOINS.contains(&asset) { Decimal::ONE } else { Decimal::ZERO })
+  }
+
+  /// 记录权益曲线, 首次记录时按当前价格估值初始钱包余额作为初始权益
+  pub fn record_equity(&mut self, time: DateTime<Utc>) {
+    if self.initial_equity.is_none() {
+      let balances =
+        self.config.balances.iter().map(|(k, v)| v * self.asset_price(k)).sum::<Decimal>();
+      self.initial_equity = Some(self.config.cash + balances);
+    }
+    let equity = self.account.equity();
+    self.equity.push((time, equity));
+  }
+
+  /// 初始权益, 尚未记录权益曲线时为资金
+  pub fn start_equity(&self) -> Decimal {
+    self.initial_equity.unwrap_or(self.config.cash)
+  }
+
+  /// 成交记录, 盈亏及手续费按成交时的结算资产价格折算为资金资产
+  pub fn cash_trades(&self) -> Vec<Trade> {
+    self
+      .trades
+      .iter()
+      .map(|v| Trade { pnl: v.pnl * v.settle_price, fee: v.fee * v.settle_price, ..v.clone() })
+      .collect()
+  }
+
+  /// 交易对可用保证金, 以结算资产计
+  /// ---
+  /// 联合保证金模式下U本位合约为可用资金, 其他为结算资产可用余额
+  pub fn available(&self, pair: &Pair) -> Decimal {
+    if self.config.multi_assets && pair.info.market == Market::UsdM {
+      return self.account.available_cash;
+    }
+    self.account.available_balances.get(&pair.info.settle_asset).cloned().unwrap_or_default()
+  }
+
+  /// 按标记价格刷新仓位盈亏及账户资金
+  /// ---
+  /// - 现货持仓记录持仓成本, 盈亏不计入账户未实现盈亏, 资产按钱包余额估值
+  /// - 现货买单冻结计价资产, 卖单冻结基础资产
+  /// - 合约盈亏、保证金及冻结按结算资产计入可用余额, 账户保证金及盈亏按结算资产价格折算
+  /// - 联合保证金模式下U本位合约共用可用资金 : 抵押资产按折价率折算后加上U本位合约盈亏, 扣除保证金及冻结
+  /// - 逐仓盈亏不计入可用余额
+  /// - 逐仓强平价格按仓位保证金计算, 全仓按可用余额加仓位保证金计算
+  pub fn refresh_account(&mut self) {
+    // 合约按(是否币本位, 结算资产)汇总保证金、盈亏、全仓盈亏及冻结
+    let mut pools = BTreeMap::<(bool, String), (Decimal, Decimal, Decimal, Decimal)>::new();
+    let mut frozen = BTreeMap::<String, Decimal>::new();
+    for pair in self.pairs.values_mut() {
+      for position in [&mut pair.long, &mut pair.short] {
+        position.mark_price = pair.mark_price;
+        position.pnl = if position.size.is_zero() {
+          Decimal::ZERO
+        } else {
+          pair.info.pnl(position.side, position.price, pair.mark_price, position.size)
+        };
+      }
+      pair.margin = pair.long.margin + pair.short.margin;
+      let orders = pair.orders.values().filter(|o| o.is_open());
+      if pair.is_spot() {
+        for order in orders {
+          if !order.reduce {
+            *frozen.entry(pair.info.settle_asset.clone()).or_default() += order.margin;
+          } else if order.is_accepted() {
+            *frozen.entry(pair.info.base_asset.clone()).or_default() += order.remain_size();
+          }
+        }
+      } else {
+        let pool = pools.entry((pair.is_inverse(), pair.info.settle_asset.clone())).or_default();
+        pool.0 += pair.margin;
+        pool.1 += pair.long.pnl + pair.short.pnl;
+        if !pair.is_isolated() {
+          pool.2 += pair.long.pnl + pair.short.pnl;
+        }
+        pool.3 += orders.map(|o| o.margin).sum::<Decimal>();
+      }
+    }
+
+    let mut available = self.account.balances.clone();
+    for (asset, v) in frozen {
+      *available.entry(asset).or_default() -= v;
+    }
+    let (mut margin, mut pnl, mut shared) = (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+    for ((inverse, asset), (m, p, cross, f)) in pools {
+      let price = self.asset_price(&asset);
+      margin += m * price;
+      pnl += p * price;
+      if self.config.multi_assets && !inverse {
+        shared += (cross - m - f) * price;
+      } else {
+        *available.entry(asset).or_default() += cross - m - f;
+      }
+    }
+
+    self.account.available_cash = if self.config.multi_assets {
+      let collateral = available
+        .iter()
+        .filter_map(|(k, v)| {
+          let haircut = match self.config.haircuts.get(k) {
+            _ if k == CASH_ASSET => Decimal::ZERO,
+            Some(haircut) => *haircut,
+            None => return None,
+          };
+          Some(*v * self.asset_price(k) * (Decimal::ONE - haircut))
+        })
+        .sum::<Decimal>();
+      collateral + shared
+    } else {
+      available.get(CASH_ASSET).cloned().unwrap_or_default()
+    };
+    self.account.cash =
+      self.account.balances.iter().map(|(k, v)| v * self.asset_price(k)).sum::<Decimal>();
+    self.account.margin = margin;
+    self.account.pnl = pnl;
+    self.account.available_balances = available;
+
+    // 强平价格
+    let prices = self
+      .pairs
+      .values()
+      .map(|v| (v.info.settle_asset.clone(), self.asset_price(&v.info.settle_asset)))
+      .collect::<BTreeMap<_, _>>();
+    for pair in self.pairs.values_mut().filter(|v| !v.is_spot()) {
+      let free = if self.config.multi_assets && pair.info.market == Market::UsdM {
+        let price = prices.get(&pair.info.settle_asset).cloned().unwrap_or_default();
+        if price.is_zero() {
+          Decimal::ZERO
+        } else {
+          self.account.available_cash / price
+        }
+      } else {
+        self.account.available_balances.get(&pair.info.settle_asset).cloned().unwrap_or_default()
+      };
+      for position in [&mut pair.long, &mut pair.short] {
+        let margin = match pair.margin_mode {
+          MarginMode::Isolated => position.margin,
+          MarginMode::Cross => free.max(Decimal::ZERO) + position.margin - position.pnl,
+        };
+        position.liquidation_price = pair.info.liquidation_price(
+          position.side,
+          position.price,
+          position.size,
+          margin,
+          self.config.maintenance_rate,
+        );
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::SymbolInfo;
+  use crate::types::{Market, Side};
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let d = |v: &str| v.parse::<Decimal>().unwrap();
+    let info = SymbolInfo {
+      symbol: "BTCUSDT".to_owned(),
+      step_size: d("0.001"),
+      min_size: d("0.001"),
+      max_size: d("1000"),
+      max_leverage: d("125"),
+      brackets: vec![(d("50000"), d("125")), (d("250000"), d("100"))],
+      ..Default::default()
+    };
+    assert_eq!(info.round_size(d("0.12345")), d("0.123"));
+    assert_eq!(info.round_size(d("0.0009")), Decimal::ZERO);
+    assert_eq!(info.round_size(d("5000")), d("1000"));
+    assert_eq!(info.bracket_leverage(d("50000")), d("125"));
+    assert_eq!(info.bracket_leverage(d("50001")), d("100"));
+    assert_eq!(info.bracket_leverage(d("1000000")), d("100"));
+    assert_eq!(info.liquidation_price(Side::Long, d("100"), d("1"), d("10"), d("0")), d("90"));
+    assert_eq!(info.liquidation_price(Side::Short, d("100"), d("2"), d("20"), d("0")), d("110"));
+
+    let inverse =
+      SymbolInfo { market: Market::CoinM, contract_size: d("100"), ..Default::default() };
+    assert_eq!(inverse.notional(d("50000"), d("10")), d("0.02"));
+    assert_eq!(inverse.pnl(Side::Long, d("40000"), d("50000"), d("10")), d("0.005"));
+    assert_eq!(inverse.pnl(Side::Short, d("40000"), d("50000"), d("10")), d("-0.005"));
+    assert_eq!(inverse.avg_price(d("20000"), d("20"), d("30000"), d("30")), d("25000"));
+    let price = inverse.liquidation_price(Side::Long, d("50000"), d("10"), d("0.005"), d("0"));
+    assert_eq!(price, d("40000"));
+    Ok(())
+  }
 }
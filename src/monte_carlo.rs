@@ -0,0 +1,155 @@
+use crate::{models::Trade, types::Resample};
+use anyhow::{ensure, Result};
+use pyo3::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// 百分位
+pub const PERCENTILES: [f64; 5] = [0.05, 0.25, 0.5, 0.75, 0.95];
+
+/// 单次模拟结果
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+  /// 最终收益率
+  pub final_return: f64,
+  /// 最大回撤
+  pub max_drawdown: f64,
+  /// 是否破产
+  pub ruined: bool,
+}
+
+/// 蒙特卡洛分析摘要
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonteCarlo {
+  /// 重采样方式
+  pub method: Resample,
+  /// 模拟次数
+  pub runs: usize,
+  /// 百分位, 与收益率及回撤一一对应
+  pub percentiles: Vec<f64>,
+  /// 最终收益率百分位
+  pub final_return: Vec<f64>,
+  /// 最大回撤百分位
+  pub max_drawdown: Vec<f64>,
+  /// 破产概率
+  pub risk_of_ruin: f64,
+}
+
+/// 按重采样后的成交序列模拟一次权益曲线
+/// ---
+/// - `pnls` 为每笔成交扣除手续费后的盈亏
+/// - 权益任一时刻低于 `initial * (1 - ruin)` 视为破产
+pub fn simulate<R: Rng>(
+  initial: f64,
+  pnls: &[f64],
+  method: Resample,
+  skip_rate: f64,
+  ruin: f64,
+  rng: &mut R,
+) -> Sample {
+  let pnls = match method {
+    Resample::Shuffle => {
+      let mut pnls = pnls.to_vec();
+      pnls.shuffle(rng);
+      pnls
+    },
+    Resample::Bootstrap => (0..pnls.len()).filter_map(|_| pnls.choose(rng).copied()).collect(),
+    Resample::Skip => pnls.iter().filter(|_| !rng.gen_bool(skip_rate)).copied().collect(),
+  };
+
+  let floor = initial * (1.0 - ruin);
+  let mut equity = initial;
+  let mut peak = initial;
+  let mut max_drawdown = 0.0f64;
+  let mut ruined = false;
+  for pnl in pnls {
+    equity += pnl;
+    peak = peak.max(equity);
+    if peak > 0.0 {
+      max_drawdown = max_drawdown.max((peak - equity) / peak);
+    }
+    ruined |= equity <= floor;
+  }
+  let final_return = if initial > 0.0 { equity / initial - 1.0 } else { 0.0 };
+  Sample { final_return, max_drawdown, ruined }
+}
+
+/// 已排序序列的百分位, 线性插值
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+  if sorted.is_empty() {
+    return 0.0;
+  }
+  let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+  let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+  sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// 蒙特卡洛分析
+/// ---
+/// - 对成交记录重采样 `runs` 次, 由rayon线程池并行模拟
+/// - 每次模拟的种子由运行随机数生成器派生, 结果可复现
+/// - 返回每次模拟的结果及摘要
+pub fn monte_carlo(
+  initial: f64,
+  trades: &[Trade],
+  method: Resample,
+  runs: usize,
+  skip_rate: f64,
+  ruin: f64,
+) -> Result<(Vec<Sample>, MonteCarlo)> {
+  ensure!(runs > 0, "模拟次数错误 : {runs}");
+  ensure!((0.0..1.0).contains(&skip_rate), "跳过比例错误 : {skip_rate}");
+  ensure!(ruin > 0.0 && ruin <= 1.0, "破产阈值错误 : {ruin}");
+  ensure!(!trades.is_empty(), "没有成交记录");
+  crate::helpers::runtime::init_rayon()?;
+
+  let pnls =
+    trades.iter().map(|v| (v.pnl - v.fee).to_f64().unwrap_or_default()).collect::<Vec<_>>();
+  let seed = crate::helpers::random::with_rng(|rng| rng.gen::<u64>());
+  let samples = (0..runs)
+    .into_par_iter()
+    .map(|i| {
+      let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+      simulate(initial, &pnls, method, skip_rate, ruin, &mut rng)
+    })
+    .collect::<Vec<_>>();
+
+  let sorted = |f: fn(&Sample) -> f64| {
+    let mut values = samples.iter().map(f).collect::<Vec<_>>();
+    values.sort_by(f64::total_cmp);
+    PERCENTILES.iter().map(|p| percentile(&values, *p)).collect::<Vec<_>>()
+  };
+  let summary = MonteCarlo {
+    method,
+    runs,
+    percentiles: PERCENTILES.to_vec(),
+    final_return: sorted(|v| v.final_return),
+    max_drawdown: sorted(|v| v.max_drawdown),
+    risk_of_ruin: samples.iter().filter(|v| v.ruined).count() as f64 / runs as f64,
+  };
+  Ok((samples, summary))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::types::Resample;
+  use anyhow::Result;
+  use rand::{rngs::StdRng, SeedableRng};
+
+  #[test]
+  fn tests() -> Result<()> {
+    assert_eq!(super::percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 0.5), 3.0);
+    assert_eq!(super::percentile(&[0.0, 10.0], 0.25), 2.5);
+
+    // 打乱顺序不影响最终收益, 只影响回撤
+    let pnls = [50.0, -30.0, 20.0, -60.0];
+    let mut rng = StdRng::seed_from_u64(0);
+    let sample = super::simulate(100.0, &pnls, Resample::Shuffle, 0.0, 0.5, &mut rng);
+    assert!((sample.final_return + 0.2).abs() < 1e-9);
+    assert!(sample.max_drawdown > 0.0);
+    Ok(())
+  }
+}
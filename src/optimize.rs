@@ -0,0 +1,183 @@
+use crate::{
+  engine::Strategy, global::engine, helpers::constants::Environment, models::Config,
+  report::Report, types::Mode,
+};
+use anyhow::{anyhow, ensure, Result};
+use chrono::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// 子进程环境变量 : 策略文件
+const WORKER_STRATEGY: &str = "WUKONG_WORKER_STRATEGY";
+/// 子进程环境变量 : 参数
+const WORKER_PARAMS: &str = "WUKONG_WORKER_PARAMS";
+/// 子进程环境变量 : 运行配置
+const WORKER_CONFIG: &str = "WUKONG_WORKER_CONFIG";
+/// 子进程环境变量 : 是否输出权益曲线
+const WORKER_EQUITY: &str = "WUKONG_WORKER_EQUITY";
+/// 子进程输出结果的前缀
+const OUTPUT_PREFIX: &str = "WUKONG_OUTPUT:";
+
+/// 参数组合, 值为JSON
+pub type Params = Vec<(String, String)>;
+
+/// 子进程回测结果
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WorkerOutput {
+  pub report: Report,
+  /// 权益曲线, 仅在要求时输出
+  pub equity: Vec<(DateTime<Utc>, Decimal)>,
+}
+
+/// 优化结果
+pub struct OptimizeResult {
+  pub params: Params,
+  pub report: Report,
+}
+
+/// 参数网格的全部组合
+pub fn combinations(grid: &[(String, Vec<String>)]) -> Vec<Params> {
+  grid.iter().fold(vec![vec![]], |combos, (name, values)| {
+    combos
+      .iter()
+      .flat_map(|combo| {
+        values.iter().map(move |value| {
+          let mut combo = combo.clone();
+          combo.push((name.clone(), value.clone()));
+          combo
+        })
+      })
+      .collect()
+  })
+}
+
+/// 指标是否越小越好
+pub fn lower_is_better(metric: &str) -> bool {
+  matches!(metric, "max_drawdown" | "fees")
+}
+
+/// 参数优化
+/// ---
+/// - 网格搜索, 指定 `samples` 时随机抽取N个组合, 按 `config.seed` 抽样
+/// - 每个组合在独立的Python子进程中回测, 由rayon线程池并行调度
+/// - 结果按指标排序, 失败的组合记录日志后跳过
+pub fn optimize(
+  python: &str,
+  strategy: &str,
+  grid: &[(String, Vec<String>)],
+  metric: &str,
+  config: &Config,
+  samples: Option<usize>,
+) -> Result<Vec<OptimizeResult>> {
+  ensure!(Report::default().metric(metric).is_some(), "指标不存在 : {metric}");
+  crate::helpers::runtime::init_rayon()?;
+  let mut combos = combinations(grid);
+  if let Some(samples) = samples {
+    let mut rng = match config.seed {
+      Some(seed) => StdRng::seed_from_u64(seed),
+      None => StdRng::from_entropy(),
+    };
+    combos = combos.choose_multiple(&mut rng, samples).cloned().collect();
+  }
+  tracing::info!("参数优化 : {} 个组合", combos.len());
+
+  // 交易对规则由主进程写入, 子进程只读打开数据库
+  let mut config = config.clone();
+  if let Some(file) = config.symbol_file.take() {
+    crate::engine::release()?;
+    crate::symbol::load_file(&crate::helpers::database::open(Mode::Backtest)?, file)?;
+  }
+  let config = serde_json::to_string(&config)?;
+
+  let mut results = combos
+    .into_par_iter()
+    .filter_map(|params| match backtest(python, strategy, &params, &config, false) {
+      Ok(output) => Some(OptimizeResult { params, report: output.report }),
+      Err(e) => {
+        tracing::warn!("参数组合回测失败 : {:?} {}", params, e);
+        None
+      },
+    })
+    .collect::<Vec<_>>();
+
+  let value = |v: &OptimizeResult| v.report.metric(metric).unwrap_or_default();
+  results.sort_by(|a, b| {
+    if lower_is_better(metric) {
+      value(a).total_cmp(&value(b))
+    } else {
+      value(b).total_cmp(&value(a))
+    }
+  });
+  Ok(results)
+}
+
+/// 在子进程中回测一组参数, `config` 为JSON
+pub fn backtest(
+  python: &str,
+  strategy: &str,
+  params: &Params,
+  config: &str,
+  equity: bool,
+) -> Result<WorkerOutput> {
+  let output = Command::new(python)
+    .args(["-c", "from wukong import run_worker; run_worker()"])
+    .env(WORKER_STRATEGY, strategy)
+    .env(WORKER_PARAMS, serde_json::to_string(params)?)
+    .env(WORKER_CONFIG, config)
+    .env(WORKER_EQUITY, equity.to_string())
+    .env(Environment::ShowBanner.to_string(), "false")
+    .env(Environment::LogLevel.to_string(), "warn")
+    .output()?;
+  ensure!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  let output = stdout
+    .lines()
+    .find_map(|v| v.strip_prefix(OUTPUT_PREFIX))
+    .ok_or(anyhow!("子进程未输出回测结果"))?;
+  Ok(serde_json::from_str(output)?)
+}
+
+/// 子进程入口, 按环境变量运行回测并输出回测结果
+pub fn run_worker() -> Result<()> {
+  let var = |name: &str| std::env::var(name).map_err(|_| anyhow!("缺少环境变量 : {name}"));
+  let path = var(WORKER_STRATEGY)?;
+  let params = serde_json::from_str::<Params>(&var(WORKER_PARAMS)?)?;
+  let config = serde_json::from_str::<Config>(&var(WORKER_CONFIG)?)?;
+
+  let strategy = Strategy { path, cash: config.cash, params };
+  crate::engine::start(Mode::Backtest, vec![strategy], config, true)?;
+
+  let output = {
+    let engine = engine();
+    let engine = engine.lock();
+    WorkerOutput {
+      report: Report::from_context(&engine),
+      equity: if var(WORKER_EQUITY)? == "true" { engine.equity.clone() } else { vec![] },
+    }
+  };
+  println!("{OUTPUT_PREFIX}{}", serde_json::to_string(&output)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use anyhow::Result;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let grid = vec![
+      ("fast".to_owned(), vec!["5".to_owned(), "10".to_owned()]),
+      ("slow".to_owned(), vec!["20".to_owned(), "30".to_owned(), "60".to_owned()]),
+    ];
+    let combos = super::combinations(&grid);
+    assert_eq!(combos.len(), 6);
+    assert_eq!(
+      combos[1],
+      vec![("fast".to_owned(), "5".to_owned()), ("slow".to_owned(), "30".to_owned())]
+    );
+    Ok(())
+  }
+}
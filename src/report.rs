@@ -0,0 +1,207 @@
+use crate::{
+  models::{Context, Trade},
+  monte_carlo::MonteCarlo,
+};
+use chrono::prelude::*;
+use pyo3::prelude::*;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 绩效报告
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Report {
+  /// 初始权益
+  pub initial_equity: f64,
+  /// 最终权益
+  pub final_equity: f64,
+  /// 总收益率
+  pub total_return: f64,
+  /// 年化收益率
+  pub annual_return: f64,
+  /// 最大回撤
+  pub max_drawdown: f64,
+  /// 夏普比率, 按日收益率年化
+  pub sharpe: f64,
+  /// 成交次数
+  pub trades: usize,
+  /// 胜率, 按平仓成交统计
+  pub win_rate: f64,
+  /// 盈亏比, 平仓总盈利 / 平仓总亏损, 有盈利且无亏损时为无穷大
+  #[serde(serialize_with = "serialize_f64", deserialize_with = "deserialize_f64")]
+  pub profit_factor: f64,
+  /// 手续费, 以资金资产计
+  pub fees: f64,
+  /// 蒙特卡洛分析摘要, 运行分析后才有
+  pub monte_carlo: Option<MonteCarlo>,
+}
+
+impl Report {
+  /// 按权益曲线及成交记录计算
+  pub fn new(initial: Decimal, equity: &[(DateTime<Utc>, Decimal)], trades: &[Trade]) -> Self {
+    let f = |v: Decimal| v.to_f64().unwrap_or_default();
+    let initial_equity = f(initial);
+    let final_equity = equity.last().map(|v| f(v.1)).unwrap_or(initial_equity);
+    let total_return = if initial_equity > 0.0 { final_equity / initial_equity - 1.0 } else { 0.0 };
+
+    // 最大回撤
+    let mut peak = initial_equity;
+    let mut max_drawdown = 0.0f64;
+    for (_, v) in equity.iter() {
+      let v = f(*v);
+      peak = peak.max(v);
+      if peak > 0.0 {
+        max_drawdown = max_drawdown.max((peak - v) / peak);
+      }
+    }
+
+    // 日收益率
+    let mut daily = vec![];
+    let mut last = initial_equity;
+    for (i, (time, v)) in equity.iter().enumerate() {
+      let day_end = equity.get(i + 1).is_none_or(|n| n.0.date_naive() != time.date_naive());
+      if day_end {
+        let v = f(*v);
+        if last > 0.0 {
+          daily.push(v / last - 1.0);
+        }
+        last = v;
+      }
+    }
+    let days = daily.len() as f64;
+    let annual_return = if days > 0.0 && total_return > -1.0 {
+      (1.0 + total_return).powf(365.0 / days) - 1.0
+    } else {
+      0.0
+    };
+    let sharpe = if daily.len() > 1 {
+      let mean = daily.iter().sum::<f64>() / days;
+      let std = (daily.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (days - 1.0)).sqrt();
+      if std > 0.0 {
+        mean / std * 365f64.sqrt()
+      } else {
+        0.0
+      }
+    } else {
+      0.0
+    };
+
+    // 成交
+    let closed = trades.iter().filter(|v| v.reduce).collect::<Vec<_>>();
+    let wins = closed.iter().filter(|v| v.pnl.is_sign_positive() && !v.pnl.is_zero()).count();
+    let win_rate = if closed.is_empty() { 0.0 } else { wins as f64 / closed.len() as f64 };
+    let profit = closed.iter().map(|v| v.pnl.max(Decimal::ZERO)).sum::<Decimal>();
+    let loss = closed.iter().map(|v| (-v.pnl).max(Decimal::ZERO)).sum::<Decimal>();
+    let profit_factor = match (profit.is_zero(), loss.is_zero()) {
+      (true, true) => 0.0,
+      (false, true) => f64::INFINITY,
+      _ => f(profit / loss),
+    };
+
+    Self {
+      initial_equity,
+      final_equity,
+      total_return,
+      annual_return,
+      max_drawdown,
+      sharpe,
+      trades: trades.len(),
+      win_rate,
+      profit_factor,
+      fees: f(trades.iter().map(|v| v.fee).sum()),
+      monte_carlo: None,
+    }
+  }
+
+  /// 按上下文计算, 成交盈亏及手续费按成交时的结算资产价格折算为资金资产
+  pub fn from_context(ctx: &Context) -> Self {
+    let trades = ctx.cash_trades();
+    Self {
+      monte_carlo: ctx.monte_carlo.clone(),
+      ..Self::new(ctx.start_equity(), &ctx.equity, &trades)
+    }
+  }
+
+  /// 全部指标
+  pub fn metrics(&self) -> Vec<(&'static str, f64)> {
+    vec![
+      ("initial_equity", self.initial_equity),
+      ("final_equity", self.final_equity),
+      ("total_return", self.total_return),
+      ("annual_return", self.annual_return),
+      ("max_drawdown", self.max_drawdown),
+      ("sharpe", self.sharpe),
+      ("trades", self.trades as f64),
+      ("win_rate", self.win_rate),
+      ("profit_factor", self.profit_factor),
+      ("fees", self.fees),
+    ]
+  }
+
+  /// 按名称获取指标
+  pub fn metric(&self, name: &str) -> Option<f64> {
+    self.metrics().into_iter().find(|v| v.0 == name).map(|v| v.1)
+  }
+}
+
+/// JSON不支持非有限值, 序列化为字符串
+fn serialize_f64<S: Serializer>(v: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+  if v.is_finite() {
+    serializer.serialize_f64(*v)
+  } else {
+    serializer.serialize_str(&v.to_string())
+  }
+}
+
+fn deserialize_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+  match serde_json::Value::deserialize(deserializer)? {
+    serde_json::Value::Number(v) => v.as_f64(),
+    serde_json::Value::String(v) => v.parse::<f64>().ok(),
+    _ => None,
+  }
+  .ok_or(serde::de::Error::custom("数值格式错误"))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{helpers::date::str_to_date, models::Trade, types::Side};
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let equity = vec![
+      (str_to_date("20240101")?, Decimal::from(100)),
+      (str_to_date("20240102")?, Decimal::from(120)),
+      (str_to_date("20240103")?, Decimal::from(90)),
+      (str_to_date("20240104")?, Decimal::from(110)),
+    ];
+    let report = super::Report::new(Decimal::from(100), &equity, &[]);
+    assert!((report.total_return - 0.1).abs() < 1e-9);
+    assert!((report.max_drawdown - 0.25).abs() < 1e-9);
+    assert_eq!(report.metric("trades"), Some(0.0));
+
+    // 全部盈利时盈亏比为无穷大, JSON往返不变
+    let trade = Trade {
+      symbol: "BTCUSDT".to_owned(),
+      order_id: "1".to_owned(),
+      time: str_to_date("20240102")?,
+      side: Side::Long,
+      reduce: true,
+      price: Decimal::from(100),
+      size: Decimal::ONE,
+      fee: Decimal::ZERO,
+      fee_asset: "USDT".to_owned(),
+      pnl: Decimal::from(10),
+      settle_price: Decimal::ONE,
+      maker: false,
+      intrabar: None,
+    };
+    let report = super::Report::new(Decimal::from(100), &equity, &[trade]);
+    assert_eq!(report.profit_factor, f64::INFINITY);
+    let json = serde_json::to_string(&report)?;
+    let report = serde_json::from_str::<super::Report>(&json)?;
+    assert_eq!(report.profit_factor, f64::INFINITY);
+    Ok(())
+  }
+}
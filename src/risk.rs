@@ -0,0 +1,177 @@
+use crate::models::{Context, Order};
+use anyhow::{ensure, Result};
+use chrono::{prelude::*, Duration};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+
+/// 风控状态
+#[derive(Debug, Default)]
+pub struct RiskState {
+  /// 最近一分钟内的下单时间
+  orders: VecDeque<DateTime<Utc>>,
+  /// 当日开始时间
+  day: Option<NaiveDate>,
+  /// 当日开始权益, 按引擎时钟跨日时记录
+  day_equity: Decimal,
+  /// 最高权益
+  peak_equity: Decimal,
+}
+
+impl RiskState {
+  /// 记录下单, 并移除一分钟前的记录
+  pub fn record(&mut self, time: DateTime<Utc>) {
+    self.orders.push_back(time);
+    self.prune(time);
+  }
+
+  /// 最近一分钟下单数
+  fn order_rate(&mut self, time: DateTime<Utc>) -> usize {
+    self.prune(time);
+    self.orders.len()
+  }
+
+  /// 移除一分钟前的下单记录
+  fn prune(&mut self, time: DateTime<Utc>) {
+    while self.orders.front().is_some_and(|v| time - *v >= Duration::minutes(1)) {
+      self.orders.pop_front();
+    }
+  }
+
+  /// 相对最高权益的回撤比例
+  pub fn drawdown(&mut self, equity: Decimal) -> Decimal {
+    self.peak_equity = self.peak_equity.max(equity);
+    if self.peak_equity.is_zero() {
+      return Decimal::ZERO;
+    }
+    (self.peak_equity - equity) / self.peak_equity
+  }
+
+  /// 重置最高权益
+  pub fn reset_peak(&mut self, equity: Decimal) {
+    self.peak_equity = equity;
+  }
+
+  /// 跨日时记录当日开始权益, 每分钟开始时调用
+  pub fn roll_day(&mut self, time: DateTime<Utc>, equity: Decimal) {
+    if self.day != Some(time.date_naive()) {
+      self.day = Some(time.date_naive());
+      self.day_equity = equity;
+    }
+  }
+
+  /// 当日亏损, 尚未记录当日开始权益时为0
+  fn daily_loss(&self, equity: Decimal) -> Decimal {
+    if self.day.is_none() {
+      return Decimal::ZERO;
+    }
+    self.day_equity - equity
+  }
+}
+
+/// 下单前风控检查
+/// ---
+/// - 减仓订单仅检查订单数量、下单频率及价格偏离
+/// - 修改订单时按修改后的订单检查, 有效订单数及名义价值不重复计入原订单
+pub fn check(ctx: &mut Context, order: &Order) -> Result<()> {
+  let limits = ctx.config.risk.clone();
+  let time = ctx.trade_time;
+  let equity = ctx.account.equity();
+
+  // 有效订单数
+  if let Some(max) = limits.max_open_orders {
+    let count: usize = ctx
+      .pairs
+      .values()
+      .map(|v| v.orders.values().filter(|o| o.is_open() && o.id != order.id).count())
+      .sum();
+    ensure!(count < max, "有效订单数超限 : {count} >= {max}");
+  }
+
+  // 下单频率
+  if let Some(max) = limits.max_order_rate {
+    let rate = ctx.risk.order_rate(time);
+    ensure!(rate < max, "下单频率超限 : {rate} >= {max}/分钟");
+  }
+
+  // 价格偏离
+  if let (Some(band), Some(pair)) = (limits.price_band, ctx.pairs.get(&order.symbol)) {
+    if !pair.mark_price.is_zero() {
+      let deviation = (order.price - pair.mark_price).abs() / pair.mark_price;
+      ensure!(deviation <= band, "价格偏离标记价格超限 : {deviation} > {band}");
+    }
+  }
+
+  if order.reduce {
+    return Ok(());
+  }
+
+  // 单日亏损
+  if let Some(max) = limits.max_daily_loss {
+    let loss = ctx.risk.daily_loss(equity);
+    ensure!(loss < max, "单日亏损超限 : {loss} >= {max}");
+  }
+
+  let notional = match ctx.pairs.get(&order.symbol) {
+    Some(pair) => pair.info.unit_value(order.price) * order.remain_size(),
+    None => order.price * order.remain_size(),
+  };
+
+  // 单交易对名义价值
+  if let (Some(max), Some(pair)) = (limits.max_notional, ctx.pairs.get(&order.symbol)) {
+    let total = pair_notional(ctx, &order.symbol, &order.id) + notional;
+    ensure!(total <= max, "{} 名义价值超限 : {total} > {max}", pair.symbol);
+  }
+
+  // 账户总杠杆
+  if let Some(max) = limits.max_leverage {
+    let total =
+      ctx.pairs.keys().map(|v| pair_notional(ctx, v, &order.id)).sum::<Decimal>() + notional;
+    ensure!(equity.is_sign_positive() && !equity.is_zero(), "账户权益不足 : {equity}");
+    let leverage = total / equity;
+    ensure!(leverage <= max, "账户总杠杆超限 : {leverage} > {max}");
+  }
+
+  Ok(())
+}
+
+/// 交易对名义价值 (持仓及有效开仓订单), 以计价资产计, 不含订单 `excluded`
+fn pair_notional(ctx: &Context, symbol: &str, excluded: &str) -> Decimal {
+  let Some(pair) = ctx.pairs.get(symbol) else {
+    return Decimal::ZERO;
+  };
+  let positions = (pair.long.size + pair.short.size) * pair.info.unit_value(pair.mark_price);
+  let orders = pair
+    .orders
+    .values()
+    .filter(|o| o.is_open() && !o.reduce && o.id != excluded)
+    .map(|o| pair.info.unit_value(o.price) * o.remain_size())
+    .sum::<Decimal>();
+  positions + orders
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::helpers::date::str_to_date;
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let mut risk = super::RiskState::default();
+    assert_eq!(risk.daily_loss(Decimal::from(900)), Decimal::ZERO);
+
+    // 当日开始权益在跨日时记录, 首次下单前的亏损同样计入
+    risk.roll_day(str_to_date("202402070000")?, Decimal::from(1000));
+    risk.roll_day(str_to_date("202402070537")?, Decimal::from(800));
+    assert_eq!(risk.daily_loss(Decimal::from(800)), Decimal::from(200));
+    risk.roll_day(str_to_date("202402080000")?, Decimal::from(800));
+    assert_eq!(risk.daily_loss(Decimal::from(750)), Decimal::from(50));
+
+    // 下单记录只保留最近一分钟
+    for time in ["202402080000", "202402080000", "202402080001", "202402080130"] {
+      risk.record(str_to_date(time)?);
+    }
+    assert_eq!(risk.orders.len(), 1);
+    Ok(())
+  }
+}
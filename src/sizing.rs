@@ -0,0 +1,184 @@
+use crate::{
+  models::{Context, Pair},
+  types::Side,
+};
+use anyhow::{anyhow, ensure, Result};
+use rust_decimal::Decimal;
+
+/// 按资金计算下单数量
+/// ---
+/// 数量 = 资金 * 杠杆 / 标记价格, 不超过可用资金可开数量
+/// ---
+/// 币本位合约按合约面值计算张数
+pub fn size_by_cash(ctx: &Context, symbol: &str, cash: Decimal) -> Result<Decimal> {
+  let pair = pair(ctx, symbol)?;
+  let unit = pair.info.unit_value(pair.mark_price);
+  ensure!(!unit.is_zero(), "{symbol} 合约面值未知");
+  let size = cash * pair.leverage / unit;
+  Ok(round(pair, size.min(max_size(ctx, pair))))
+}
+
+/// 按风险计算下单数量
+/// ---
+/// 数量 = 可用保证金 * 风险比例 / 单位数量止损亏损, 不超过可用资金可开数量
+/// ---
+/// 止损价做多为 `标记价格 - 止损距离`, 做空为 `标记价格 + 止损距离`, 单位亏损按方向以结算资产计,
+/// 币本位合约按合约面值计算, 多空不对称
+pub fn size_by_risk(
+  ctx: &Context,
+  symbol: &str,
+  side: Side,
+  stop_distance: Decimal,
+  risk_pct: Decimal,
+) -> Result<Decimal> {
+  let pair = pair(ctx, symbol)?;
+  ensure!(stop_distance.is_sign_positive() && !stop_distance.is_zero(), "止损距离无效");
+  let loss = unit_loss(pair, side, stop_distance)?;
+  let size = ctx.available(pair).max(Decimal::ZERO) * risk_pct / loss;
+  Ok(round(pair, size.min(max_size(ctx, pair))))
+}
+
+/// 按波动率计算下单数量
+/// ---
+/// 数量 = 可用保证金 * 目标波动率 / 单位数量ATR不利波动, 不超过可用资金可开数量
+/// ---
+/// 不利波动做多为价格下跌ATR, 做空为价格上涨ATR, 以结算资产计, 币本位合约按合约面值计算
+pub fn size_by_volatility(
+  ctx: &Context,
+  symbol: &str,
+  side: Side,
+  target_vol: Decimal,
+  atr: Decimal,
+) -> Result<Decimal> {
+  let pair = pair(ctx, symbol)?;
+  ensure!(atr.is_sign_positive() && !atr.is_zero(), "ATR无效");
+  let vol = unit_loss(pair, side, atr)?;
+  let size = ctx.available(pair).max(Decimal::ZERO) * target_vol / vol;
+  Ok(round(pair, size.min(max_size(ctx, pair))))
+}
+
+/// 价格向不利方向变动 `distance` 时单位数量的亏损, 以结算资产计
+fn unit_loss(pair: &Pair, side: Side, distance: Decimal) -> Result<Decimal> {
+  let mark = pair.mark_price;
+  let price = match side {
+    Side::Long => mark - distance,
+    Side::Short => mark + distance,
+  };
+  ensure!(price.is_sign_positive() && !price.is_zero(), "价格变动超过标记价格 : {distance}");
+  let loss = -pair.info.pnl(side, mark, price, Decimal::ONE);
+  ensure!(loss.is_sign_positive() && !loss.is_zero(), "{} 合约面值未知", pair.symbol);
+  Ok(loss)
+}
+
+/// 按凯利公式计算下单数量
+/// ---
+/// - 凯利比例 = 胜率 - (1 - 胜率) / 盈亏比, 小于0时不开仓
+/// - 数量按 可用资金 * 凯利比例 * 系数 计算
+pub fn size_by_kelly(
+  ctx: &Context,
+  symbol: &str,
+  win_rate: Decimal,
+  win_loss_ratio: Decimal,
+  fraction: Decimal,
+) -> Result<Decimal> {
+  let cash = ctx.account.available_cash * kelly(win_rate, win_loss_ratio)? * fraction;
+  size_by_cash(ctx, symbol, cash)
+}
+
+/// 凯利比例, 范围 `[0, 1]`
+fn kelly(win_rate: Decimal, win_loss_ratio: Decimal) -> Result<Decimal> {
+  ensure!(win_loss_ratio.is_sign_positive() && !win_loss_ratio.is_zero(), "盈亏比无效");
+  let f = win_rate - (Decimal::ONE - win_rate) / win_loss_ratio;
+  Ok(f.max(Decimal::ZERO).min(Decimal::ONE))
+}
+
+fn pair<'a>(ctx: &'a Context, symbol: &str) -> Result<&'a Pair> {
+  let pair = ctx.pairs.get(symbol).ok_or(anyhow!("交易对不存在 : {symbol}"))?;
+  ensure!(!pair.mark_price.is_zero(), "{symbol} 无标记价格");
+  Ok(pair)
+}
+
+/// 可用保证金可开数量
+fn max_size(ctx: &Context, pair: &Pair) -> Decimal {
+  let unit = pair.info.notional(pair.mark_price, Decimal::ONE);
+  if unit.is_zero() {
+    return Decimal::ZERO;
+  }
+  ctx.available(pair).max(Decimal::ZERO) * pair.leverage / unit
+}
+
+fn round(pair: &Pair, size: Decimal) -> Decimal {
+  pair.info.round_size(size.max(Decimal::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    helpers::date::str_to_date,
+    models::{Config, Context, Pair, SymbolInfo},
+    types::{Market, Mode, Side},
+  };
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let d = |v: &str| v.parse::<Decimal>().unwrap();
+
+    assert_eq!(super::kelly(d("0.6"), d("2"))?, d("0.4"));
+    assert_eq!(super::kelly(d("0.2"), d("1"))?, Decimal::ZERO);
+
+    // U本位 : 可用资金10000, 标记价格100
+    let time = str_to_date("202402070537")?;
+    let mut config = Config::default();
+    config.balances.insert("BTC".to_owned(), d("1"));
+    let mut ctx =
+      Context::new("test", Mode::Backtest, crate::helpers::database::open_temp()?, config, time);
+    let info = SymbolInfo {
+      symbol: "BTCUSDT".to_owned(),
+      market: Market::UsdM,
+      step_size: d("0.001"),
+      max_size: d("1000"),
+      ..Default::default()
+    };
+    let mut pair = Pair::new(info, &ctx.config);
+    pair.mark_price = d("100");
+    ctx.pairs.insert("BTCUSDT".to_owned(), pair);
+    // 币本位 : 面值100USD, 可用1BTC, 标记价格50000
+    let info = SymbolInfo {
+      symbol: "BTCUSD_PERP".to_owned(),
+      market: Market::CoinM,
+      contract_size: d("100"),
+      settle_asset: "BTC".to_owned(),
+      step_size: d("1"),
+      max_size: d("100000"),
+      ..Default::default()
+    };
+    let mut pair = Pair::new(info, &ctx.config);
+    pair.mark_price = d("50000");
+    ctx.pairs.insert("BTCUSD_PERP".to_owned(), pair);
+    ctx.refresh_account();
+
+    assert_eq!(super::size_by_cash(&ctx, "BTCUSDT", d("1000"))?, d("10"));
+    assert_eq!(super::size_by_cash(&ctx, "BTCUSDT", d("20000"))?, d("100"));
+    assert_eq!(super::size_by_risk(&ctx, "BTCUSDT", Side::Long, d("5"), d("0.01"))?, d("20"));
+    assert_eq!(super::size_by_risk(&ctx, "BTCUSDT", Side::Short, d("5"), d("0.01"))?, d("20"));
+    let size = super::size_by_volatility(&ctx, "BTCUSDT", Side::Long, d("0.02"), d("4"))?;
+    assert_eq!(size, d("50"));
+    assert_eq!(super::size_by_kelly(&ctx, "BTCUSDT", d("0.6"), d("2"), d("0.5"))?, d("20"));
+    assert_eq!(super::size_by_cash(&ctx, "BTCUSD_PERP", d("1000"))?, d("10"));
+    // 做多止损至40000每张亏损0.0005BTC, 风险0.01BTC可开20张
+    let size = super::size_by_risk(&ctx, "BTCUSD_PERP", Side::Long, d("10000"), d("0.01"))?;
+    assert_eq!(size, d("20"));
+    // 做空止损至62500每张亏损0.0004BTC, 风险0.01BTC可开25张
+    let size = super::size_by_risk(&ctx, "BTCUSD_PERP", Side::Short, d("12500"), d("0.01"))?;
+    assert_eq!(size, d("25"));
+    // 做多下跌至40000每张0.0005BTC, 做空上涨至62500每张0.0004BTC, 目标波动0.02BTC
+    let size = super::size_by_volatility(&ctx, "BTCUSD_PERP", Side::Long, d("0.02"), d("10000"))?;
+    assert_eq!(size, d("40"));
+    let size = super::size_by_volatility(&ctx, "BTCUSD_PERP", Side::Short, d("0.02"), d("12500"))?;
+    assert_eq!(size, d("50"));
+    assert!(super::size_by_risk(&ctx, "BTCUSD_PERP", Side::Long, d("50000"), d("0.01")).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,177 @@
+use crate::{helpers::database::Database, models::SymbolInfo, types::Market};
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::{path::Path, str::FromStr};
+
+/// 交易对规则表
+pub const TABLE: &str = "symbol_info";
+
+/// 解析交易所 `exchangeInfo` 数据
+/// ---
+/// - 有合约面值的为币本位合约, 有合约类型的为U本位合约, 否则为现货
+/// - 现货交易对名称为 `基础资产/计价资产`, 与同名合约区分
+pub fn parse_exchange_info(data: &Value) -> Result<Vec<SymbolInfo>> {
+  let symbols = data["symbols"].as_array().ok_or(anyhow!("exchangeInfo 缺少 symbols"))?;
+  let mut items = vec![];
+  for item in symbols {
+    let symbol = item["symbol"].as_str().ok_or(anyhow!("exchangeInfo 缺少 symbol"))?;
+    let base_asset = item["baseAsset"].as_str().unwrap_or_default().to_owned();
+    let quote_asset = item["quoteAsset"].as_str().unwrap_or_default().to_owned();
+    let market = if !item["contractSize"].is_null() {
+      Market::CoinM
+    } else if item["contractType"].is_string() {
+      Market::UsdM
+    } else {
+      Market::Spot
+    };
+    let mut info = SymbolInfo {
+      symbol: match market {
+        Market::Spot => format!("{base_asset}/{quote_asset}"),
+        Market::UsdM | Market::CoinM => symbol.to_owned(),
+      },
+      market,
+      settle_asset: item["marginAsset"].as_str().unwrap_or(&quote_asset).to_owned(),
+      contract_size: decimal(&item["contractSize"])?,
+      base_asset,
+      quote_asset,
+      contract_type: item["contractType"].as_str().unwrap_or_default().to_owned(),
+      onboard_date: item["onboardDate"].as_i64().unwrap_or_default(),
+      delist_date: item["deliveryDate"].as_i64().unwrap_or_default(),
+      ..Default::default()
+    };
+    for filter in item["filters"].as_array().into_iter().flatten() {
+      match filter["filterType"].as_str() {
+        Some("PRICE_FILTER") => {
+          info.tick_size = decimal(&filter["tickSize"])?;
+        },
+        Some("LOT_SIZE") => {
+          info.step_size = decimal(&filter["stepSize"])?;
+          info.min_size = decimal(&filter["minQty"])?;
+          info.max_size = decimal(&filter["maxQty"])?;
+        },
+        Some("MIN_NOTIONAL") if filter["notional"].is_string() => {
+          info.min_notional = decimal(&filter["notional"])?;
+        },
+        Some("MIN_NOTIONAL" | "NOTIONAL") => {
+          info.min_notional = decimal(&filter["minNotional"])?;
+        },
+        _ => {},
+      }
+    }
+    items.push(info);
+  }
+  Ok(items)
+}
+
+/// 从本地 `exchangeInfo` 快照加载
+pub fn load_file<P>(db: &Database, path: P) -> Result<usize>
+where
+  P: AsRef<Path>,
+{
+  let data = serde_json::from_str::<Value>(&std::fs::read_to_string(path)?)?;
+  let items = parse_exchange_info(&data)?;
+  save(db, &items)?;
+  Ok(items.len())
+}
+
+/// 通过网关从交易所获取U本位合约、币本位合约及现货交易对
+pub fn fetch(db: &Database) -> Result<usize> {
+  let mut items = vec![];
+  for market in [Market::UsdM, Market::CoinM, Market::Spot] {
+    items.extend(parse_exchange_info(&crate::gateway::exchange_info(market)?)?);
+  }
+  // 最大杠杆需要签名接口, 未配置API Key时跳过
+  if let Ok(gateway) = crate::global::gateway() {
+    let brackets = gateway.leverage_brackets()?;
+    for info in items.iter_mut() {
+      if let Some(brackets) = brackets.get(&info.symbol) {
+        info.max_leverage = brackets.first().map(|v| v.1).unwrap_or_default();
+        info.brackets = brackets.clone();
+      }
+    }
+  }
+  save(db, &items)?;
+  Ok(items.len())
+}
+
+/// 保存
+pub fn save(db: &Database, items: &[SymbolInfo]) -> Result<()> {
+  db.batch_set(TABLE, items.iter().map(|v| (v.symbol.clone(), v.clone())))
+}
+
+/// 获取
+pub fn get(db: &Database, symbol: &str) -> Result<Option<SymbolInfo>> {
+  db.get(TABLE, symbol)
+}
+
+/// 获取全部
+pub fn all(db: &Database) -> Result<Vec<SymbolInfo>> {
+  Ok(db.get_all::<_, String, SymbolInfo>(TABLE)?.into_iter().map(|(_, v)| v).collect())
+}
+
+fn decimal(v: &Value) -> Result<Decimal> {
+  match v {
+    Value::String(s) => Ok(Decimal::from_str(s)?),
+    Value::Number(n) => Ok(Decimal::from_str(&n.to_string())?),
+    _ => Ok(Decimal::ZERO),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::types::Market;
+  use anyhow::Result;
+  use rust_decimal::Decimal;
+  use std::str::FromStr;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let data = serde_json::json!({
+      "symbols": [{
+        "symbol": "BTCUSDT",
+        "baseAsset": "BTC",
+        "quoteAsset": "USDT",
+        "contractType": "PERPETUAL",
+        "onboardDate": 1569398400000i64,
+        "filters": [
+          { "filterType": "PRICE_FILTER", "tickSize": "0.10", "minPrice": "556.80" },
+          { "filterType": "LOT_SIZE", "stepSize": "0.001", "minQty": "0.001", "maxQty": "1000" },
+          { "filterType": "MIN_NOTIONAL", "notional": "100" }
+        ]
+      }, {
+        "symbol": "BTCUSDT",
+        "baseAsset": "BTC",
+        "quoteAsset": "USDT",
+        "filters": [
+          { "filterType": "PRICE_FILTER", "tickSize": "0.01000000" },
+          { "filterType": "LOT_SIZE", "stepSize": "0.00001000", "minQty": "0.00001000", "maxQty": "9000.00000000" },
+          { "filterType": "NOTIONAL", "minNotional": "5.00000000" }
+        ]
+      }, {
+        "symbol": "BTCUSD_PERP",
+        "baseAsset": "BTC",
+        "quoteAsset": "USD",
+        "marginAsset": "BTC",
+        "contractType": "PERPETUAL",
+        "contractSize": 100,
+        "filters": []
+      }]
+    });
+    let items = super::parse_exchange_info(&data)?;
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].tick_size, Decimal::from_str("0.1")?);
+    assert_eq!(items[0].step_size, Decimal::from_str("0.001")?);
+    assert_eq!(items[0].min_notional, Decimal::from(100));
+    assert_eq!(items[0].onboard_date, 1569398400000);
+    assert_eq!(items[1].symbol, "BTC/USDT");
+    assert_eq!(items[1].market, Market::Spot);
+    assert_eq!(items[1].exchange_symbol(), "BTCUSDT");
+    assert_eq!(items[1].min_notional, Decimal::from(5));
+    assert_eq!(items[1].settle_asset, "USDT");
+    assert_eq!(items[2].market, Market::CoinM);
+    assert_eq!(items[2].settle_asset, "BTC");
+    assert_eq!(items[2].contract_size, Decimal::from(100));
+    Ok(())
+  }
+}
@@ -1,6 +1,7 @@
-use chrono::Duration;
+use chrono::{prelude::*, Duration, Months};
 use pyo3::prelude::*;
-use strum::{AsRefStr, Display};
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, Display, FromRepr};
 
 /// 运行模式
 #[pyclass(eq, eq_int)]
@@ -34,9 +35,88 @@ pub enum Side {
   Short,
 }
 
+/// 市场类型
+#[pyclass(eq, eq_int)]
+#[derive(
+  Debug, Default, Clone, Copy, PartialEq, Display, AsRefStr, FromRepr, Serialize, Deserialize,
+)]
+#[repr(u8)]
+pub enum Market {
+  /// U本位合约
+  #[default]
+  UsdM,
+  /// 现货
+  Spot,
+  /// 币本位合约
+  CoinM,
+}
+
+/// 保证金模式
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr, Serialize, Deserialize)]
+pub enum MarginMode {
+  /// 全仓, 共用结算资产可用余额
+  Cross,
+  /// 逐仓, 亏损以仓位保证金为限
+  Isolated,
+}
+
+/// 现货手续费扣除资产
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr, Serialize, Deserialize)]
+pub enum FeeAsset {
+  /// 收到的资产, 买入扣基础资产, 卖出扣计价资产
+  Received,
+  /// 计价资产, 买单按吃单费率额外冻结手续费
+  Quote,
+}
+
+/// 回测行情回放方式
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr, Serialize, Deserialize)]
+pub enum Replay {
+  /// 分钟K线
+  Candle,
+  /// 逐笔归集成交
+  AggTrade,
+  /// 逐笔归集成交及盘口, 限价单按排队位置成交
+  Depth,
+}
+
+/// K线内价格路径假设, 决定同一根K线内挂单的成交顺序
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr, Serialize, Deserialize)]
+pub enum Intrabar {
+  /// 开盘-最高-最低-收盘
+  OpenHighLowClose,
+  /// 开盘-最低-最高-收盘
+  OpenLowHighClose,
+  /// 先到达离开盘价较近的极值
+  NearestFirst,
+  /// 先到达对持仓不利的极值, 无持仓时同 `NearestFirst`
+  Pessimistic,
+  /// 按数据库中的归集成交还原路径
+  /// ---
+  /// - 1分钟K线已是最小周期, 仅使用归集成交, 不使用更小周期K线
+  /// - 没有归集成交数据时同 `NearestFirst`, 每个交易对每天记录一次警告日志
+  DrillDown,
+}
+
+/// 成交重采样方式
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr, Serialize, Deserialize)]
+pub enum Resample {
+  /// 打乱顺序
+  Shuffle,
+  /// 有放回抽样
+  Bootstrap,
+  /// 随机跳过
+  Skip,
+}
+
 /// 时间周期
 #[pyclass(eq, eq_int)]
-#[derive(Debug, Clone, Copy, PartialEq, Display, AsRefStr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, AsRefStr)]
 pub enum TimeFrame {
   #[strum(serialize = "1m")]
   Minute,
@@ -90,6 +170,36 @@ impl TimeFrame {
       Self::Month => Duration::weeks(4),
     }
   }
+
+  /// 时间所在K线的开盘时间
+  /// ---
+  /// - 周线从周一开始
+  /// - 月线从每月1日开始
+  /// - 其他周期按 `UTC+0` 零点对齐
+  pub fn bar_begin(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+    let date = time.date_naive();
+    match self {
+      Self::Week => {
+        let date = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+        date.and_time(NaiveTime::MIN).and_utc()
+      },
+      Self::Month => date.with_day(1).unwrap_or(date).and_time(NaiveTime::MIN).and_utc(),
+      _ => {
+        let ms = self.as_duration().num_milliseconds();
+        let ts = time.timestamp_millis();
+        DateTime::from_timestamp_millis(ts - ts.rem_euclid(ms)).unwrap_or(time)
+      },
+    }
+  }
+
+  /// K线收盘时间, 即下一根K线的开盘时间
+  pub fn bar_end(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+    let begin = self.bar_begin(time);
+    match self {
+      Self::Month => begin.checked_add_months(Months::new(1)).unwrap_or(begin),
+      _ => begin + self.as_duration(),
+    }
+  }
 }
 
 /// 订单状态
@@ -111,3 +221,19 @@ pub enum OrderStatus {
   /// 已取消
   Canceled,
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::helpers::date::str_to_date;
+  use anyhow::Result;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let t = str_to_date("202402070537")?;
+    assert_eq!(super::TimeFrame::Minute15.bar_begin(t), str_to_date("202402070530")?);
+    assert_eq!(super::TimeFrame::Hour4.bar_end(t), str_to_date("202402070800")?);
+    assert_eq!(super::TimeFrame::Week.bar_begin(t), str_to_date("20240205")?);
+    assert_eq!(super::TimeFrame::Month.bar_end(t), str_to_date("20240301")?);
+    Ok(())
+  }
+}
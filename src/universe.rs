@@ -0,0 +1,68 @@
+use crate::{
+  models::{Context, SymbolInfo},
+  types::Mode,
+};
+use anyhow::Result;
+use chrono::{prelude::*, Duration};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// 选择交易对池
+/// ---
+/// - 固定交易对始终保留, 已下线的除外
+/// - 按规则从当时已上线且未下线的指定市场交易对中选择, 避免幸存者偏差
+/// - 回测按K线成交额统计, 模拟及实盘按交易所24小时成交额统计
+pub fn select(ctx: &Context, time: DateTime<Utc>) -> Result<Vec<SymbolInfo>> {
+  let now = time.timestamp_millis();
+  let infos = crate::symbol::all(&ctx.db)?
+    .into_iter()
+    .map(|v| (v.symbol.clone(), v))
+    .collect::<HashMap<_, _>>();
+
+  let mut items = ctx
+    .config
+    .symbols
+    .iter()
+    .map(|v| {
+      infos.get(v).cloned().unwrap_or(SymbolInfo { symbol: v.clone(), ..Default::default() })
+    })
+    .filter(|v| v.is_listed(now))
+    .collect::<Vec<_>>();
+
+  if let Some(rule) = &ctx.config.universe {
+    let listed_before = now - Duration::days(rule.min_listed_days).num_milliseconds();
+    let candidates = infos
+      .values()
+      .filter(|v| v.market == rule.market)
+      .filter(|v| v.is_listed(now) && v.onboard_date <= listed_before)
+      .filter(|v| rule.contract_type.as_ref().is_none_or(|c| c == &v.contract_type))
+      .collect::<Vec<_>>();
+
+    let mut volumes = match ctx.mode {
+      Mode::Backtest => {
+        let begin = now - Duration::days(1).num_milliseconds();
+        candidates
+          .par_iter()
+          .map(|v| Ok((*v, crate::market::quote_volume(&ctx.db, &v.symbol, begin, now)?)))
+          .collect::<Result<Vec<_>>>()?
+      },
+      Mode::Sandbox | Mode::Real => {
+        let volumes = crate::gateway::quote_volumes(rule.market)?;
+        candidates
+          .into_iter()
+          .map(|v| (v, volumes.get(&v.exchange_symbol()).cloned().unwrap_or(0.0)))
+          .collect()
+      },
+    };
+    volumes.retain(|(_, v)| *v > 0.0);
+    volumes.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.symbol.cmp(&b.0.symbol)));
+    if let Some(top) = rule.top {
+      volumes.truncate(top);
+    }
+    items.extend(volumes.into_iter().map(|(v, _)| v.clone()));
+  }
+
+  items.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+  items.dedup_by(|a, b| a.symbol == b.symbol);
+  Ok(items)
+}
@@ -0,0 +1,213 @@
+use crate::{
+  models::Config,
+  optimize::{self, Params},
+  report::Report,
+  types::Mode,
+};
+use anyhow::{anyhow, ensure, Result};
+use chrono::{prelude::*, Duration};
+use rust_decimal::Decimal;
+
+/// 样本窗口
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+  /// 样本内开始时间
+  pub in_begin: DateTime<Utc>,
+  /// 样本内结束时间, 即样本外开始时间
+  pub in_end: DateTime<Utc>,
+  /// 样本外结束时间
+  pub out_end: DateTime<Utc>,
+}
+
+/// 单个窗口的结果
+pub struct WindowResult {
+  pub window: Window,
+  /// 样本内最优参数
+  pub params: Params,
+  /// 样本内绩效
+  pub in_sample: Report,
+  /// 样本外绩效
+  pub out_sample: Report,
+}
+
+/// 前进分析结果
+pub struct WalkForward {
+  pub windows: Vec<WindowResult>,
+  /// 拼接后的样本外权益曲线
+  pub equity: Vec<(DateTime<Utc>, Decimal)>,
+}
+
+/// 参数稳定性
+pub struct Stability {
+  pub param: String,
+  /// 不同取值数量
+  pub distinct: usize,
+  /// 出现最多的取值, JSON
+  pub mode: String,
+  /// 出现最多的取值占比
+  pub mode_share: f64,
+}
+
+/// 划分样本窗口
+/// ---
+/// - 滚动 : 样本内窗口长度固定, 每次向前移动一个样本外长度
+/// - 锚定 : 样本内窗口始终从 `begin` 开始
+/// - 最后一个样本外窗口截断到 `end`
+pub fn windows(
+  begin: DateTime<Utc>,
+  end: DateTime<Utc>,
+  in_sample: Duration,
+  out_sample: Duration,
+  anchored: bool,
+) -> Vec<Window> {
+  let mut result = vec![];
+  let mut start = begin;
+  loop {
+    let in_end = start + in_sample;
+    if in_end >= end {
+      break;
+    }
+    let in_begin = if anchored { begin } else { start };
+    result.push(Window { in_begin, in_end, out_end: (in_end + out_sample).min(end) });
+    start += out_sample;
+  }
+  result
+}
+
+/// 前进分析
+/// ---
+/// - 每个窗口先在样本内参数优化, 再以最优参数回测样本外
+/// - 样本外权益曲线按上一窗口的期末权益缩放后拼接
+/// - 样本内没有成功的参数组合时跳过该窗口
+#[allow(clippy::too_many_arguments)]
+pub fn walk_forward(
+  python: &str,
+  strategy: &str,
+  grid: &[(String, Vec<String>)],
+  metric: &str,
+  config: &Config,
+  in_sample: Duration,
+  out_sample: Duration,
+  anchored: bool,
+  samples: Option<usize>,
+) -> Result<WalkForward> {
+  ensure!(in_sample > Duration::zero(), "样本内长度错误 : {in_sample}");
+  ensure!(out_sample > Duration::zero(), "样本外长度错误 : {out_sample}");
+  let begin = config.begin.ok_or(anyhow!("前进分析需要设置开始时间"))?;
+  let end = config.end.ok_or(anyhow!("前进分析需要设置结束时间"))?;
+  let windows = windows(begin, end, in_sample, out_sample, anchored);
+  ensure!(!windows.is_empty(), "回测区间不足一个样本窗口");
+  tracing::info!("前进分析 : {} 个窗口", windows.len());
+
+  // 交易对规则只需写入一次
+  let mut config = config.clone();
+  if let Some(file) = config.symbol_file.take() {
+    crate::engine::release()?;
+    crate::symbol::load_file(&crate::helpers::database::open(Mode::Backtest)?, file)?;
+  }
+
+  let mut result = WalkForward { windows: vec![], equity: vec![] };
+  let mut last = None;
+  for window in windows {
+    let mut in_config = config.clone();
+    in_config.begin = Some(window.in_begin);
+    in_config.end = Some(window.in_end);
+    let Some(best) =
+      optimize::optimize(python, strategy, grid, metric, &in_config, samples)?.into_iter().next()
+    else {
+      tracing::warn!("样本内无可用参数, 跳过窗口 : {:?}", window);
+      continue;
+    };
+
+    let mut out_config = config.clone();
+    out_config.begin = Some(window.in_end);
+    out_config.end = Some(window.out_end);
+    let output = optimize::backtest(
+      python,
+      strategy,
+      &best.params,
+      &serde_json::to_string(&out_config)?,
+      true,
+    )?;
+    // 按窗口初始权益衔接上一窗口结束权益
+    let initial = Decimal::try_from(output.report.initial_equity).unwrap_or_default();
+    let scale = match last {
+      Some(last) if !initial.is_zero() => last / initial,
+      _ => Decimal::ONE,
+    };
+    result.equity.extend(output.equity.iter().map(|(t, v)| (*t, v * scale)));
+    last = result.equity.last().map(|v| v.1);
+    result.windows.push(WindowResult {
+      window,
+      params: best.params,
+      in_sample: best.report,
+      out_sample: output.report,
+    });
+  }
+  Ok(result)
+}
+
+impl WalkForward {
+  /// 拼接后样本外权益曲线的绩效
+  /// ---
+  /// 初始权益取首个窗口样本外的初始权益
+  pub fn report(&self) -> Report {
+    let initial = self.windows.first().map(|v| v.out_sample.initial_equity).unwrap_or_default();
+    Report::new(Decimal::try_from(initial).unwrap_or_default(), &self.equity, &[])
+  }
+
+  /// 各参数的稳定性
+  pub fn stability(&self) -> Vec<Stability> {
+    let Some(first) = self.windows.first() else {
+      return vec![];
+    };
+    first
+      .params
+      .iter()
+      .map(|(param, _)| {
+        let mut counts: Vec<(String, usize)> = vec![];
+        for window in self.windows.iter() {
+          let Some((_, value)) = window.params.iter().find(|v| &v.0 == param) else {
+            continue;
+          };
+          match counts.iter_mut().find(|v| &v.0 == value) {
+            Some(v) => v.1 += 1,
+            None => counts.push((value.clone(), 1)),
+          }
+        }
+        let (mode, count) = counts.iter().max_by_key(|v| v.1).cloned().unwrap_or_default();
+        Stability {
+          param: param.clone(),
+          distinct: counts.len(),
+          mode,
+          mode_share: count as f64 / self.windows.len() as f64,
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::helpers::date::str_to_date;
+  use anyhow::Result;
+  use chrono::Duration;
+
+  #[test]
+  fn tests() -> Result<()> {
+    let begin = str_to_date("20240101")?;
+    let end = str_to_date("20240111")?;
+    let rolling = super::windows(begin, end, Duration::days(4), Duration::days(2), false);
+    assert_eq!(rolling.len(), 3);
+    assert_eq!(rolling[1].in_begin, str_to_date("20240103")?);
+    assert_eq!(rolling[2].in_end, str_to_date("20240109")?);
+    assert_eq!(rolling[2].out_end, end);
+
+    let anchored = super::windows(begin, end, Duration::days(4), Duration::days(4), true);
+    assert_eq!(anchored.len(), 2);
+    assert_eq!(anchored[1].in_begin, begin);
+    assert_eq!(anchored[1].in_end, str_to_date("20240109")?);
+    assert_eq!(anchored[1].out_end, end);
+    Ok(())
+  }
+}